@@ -0,0 +1,152 @@
+//! Mock BOJ login page for trying out `login` without touching acmicpc.net.
+//!
+//! Serves a login form shaped like the real one, accepting one username and password.
+//! A successful login with "remember me" checked sets the `bojautologin` cookie,
+//! and every page sets the `OnlineJudge` session cookie.
+//!
+//! ```sh
+//! cargo run --example mock_login -- 8000 alice secret
+//! # boj.toml:
+//! # [webdriver]
+//! # login_page = 'http://localhost:8000/login'
+//! gaboja login save=off
+//! ```
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+const LOGIN_FORM: &str = r#"<!DOCTYPE html>
+<html><body>
+<form method="post" action="/signin">
+  <input type="text" name="login_user_id">
+  <input type="password" name="login_password">
+  <label><input type="checkbox" name="auto_login" value="1"> 로그인 상태 유지</label>
+  <button type="submit" id="submit_button">로그인</button>
+</form>
+</body></html>"#;
+
+/// Decodes an `application/x-www-form-urlencoded` value.
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn form_field(body: &str, name: &str) -> Option<String> {
+    body.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then(|| decode(value))
+    })
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    headers: &[String],
+    body: &str,
+) -> std::io::Result<()> {
+    write!(stream, "HTTP/1.1 {}\r\n", status)?;
+    write!(stream, "Content-Type: text/html; charset=utf-8\r\n")?;
+    write!(stream, "Content-Length: {}\r\n", body.len())?;
+    write!(stream, "Connection: close\r\n")?;
+    for header in headers {
+        write!(stream, "{}\r\n", header)?;
+    }
+    write!(stream, "\r\n{}", body)
+}
+
+fn handle(mut stream: TcpStream, username: &str, password: &str) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut content_length = 0;
+    let mut cookies = String::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            match &name.to_ascii_lowercase()[..] {
+                "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                "cookie" => cookies = value.trim().to_string(),
+                _ => {}
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8_lossy(&body);
+
+    let mut headers = vec![];
+    if !cookies.contains("OnlineJudge=") {
+        headers.push("Set-Cookie: OnlineJudge=mock-session; Path=/".to_string());
+    }
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    println!("{} {}", method, path);
+    match (method, path.split('?').next().unwrap_or("")) {
+        ("POST", "/signin") => {
+            let accepted = form_field(&body, "login_user_id").as_deref() == Some(username)
+                && form_field(&body, "login_password").as_deref() == Some(password);
+            if accepted {
+                if form_field(&body, "auto_login").is_some() {
+                    headers.push(format!(
+                        "Set-Cookie: bojautologin=mock-autologin-{}; Path=/",
+                        username
+                    ));
+                }
+                headers.push("Location: /".to_string());
+            } else {
+                headers.push("Location: /login?error=1".to_string());
+            }
+            respond(&mut stream, "302 Found", &headers, "")
+        }
+        ("GET", "/login") => respond(&mut stream, "200 OK", &headers, LOGIN_FORM),
+        ("GET", "/") => {
+            let page = if cookies.contains("bojautologin=") {
+                format!(r#"<a class="username">{}</a>"#, username)
+            } else {
+                r#"<a href="/login">로그인</a>"#.to_string()
+            };
+            respond(&mut stream, "200 OK", &headers, &page)
+        }
+        _ => respond(&mut stream, "404 Not Found", &headers, "Not found"),
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let [port, username, password] = &args[..] else {
+        eprintln!("Usage: mock_login <port> <username> <password>");
+        std::process::exit(2);
+    };
+    let listener = TcpListener::bind(("127.0.0.1", port.parse::<u16>().unwrap_or(8000)))?;
+    println!("Mock login page on http://localhost:{}/login", port);
+    for stream in listener.incoming() {
+        if let Err(err) = handle(stream?, username, password) {
+            eprintln!("{}", err);
+        }
+    }
+    Ok(())
+}
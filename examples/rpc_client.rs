@@ -0,0 +1,66 @@
+//! Reference client for `gaboja serve --socket <path>`.
+//!
+//! Sends one command line to the server, prints the event notifications as they arrive
+//! and the output returned in the result,
+//! and exits with 0 on success, 1 if the tests or the submission did not pass, and 2 on error.
+//!
+//! ```sh
+//! gaboja serve --socket /tmp/gaboja.sock &
+//! cargo run --example rpc_client -- /tmp/gaboja.sock 'prob 1000'
+//! cargo run --example rpc_client -- /tmp/gaboja.sock 'test c=./a.out'
+//! ```
+
+#[cfg(unix)]
+fn main() -> std::io::Result<()> {
+    use serde_json::{json, Value};
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let [socket, command] = &args[..] else {
+        eprintln!("Usage: rpc_client <socket> <command>");
+        std::process::exit(2);
+    };
+    let mut stream = UnixStream::connect(socket)?;
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "execute",
+        "params": { "command": command },
+    });
+    writeln!(stream, "{}", request)?;
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(message) = serde_json::from_str::<Value>(&line?) else {
+            continue;
+        };
+        if message["method"] == "event" {
+            println!("{}", message["params"]);
+            continue;
+        }
+        if message["id"] != 1 {
+            continue;
+        }
+        if let Some(error) = message.get("error") {
+            eprintln!("Error: {}", error["message"].as_str().unwrap_or_default());
+            std::process::exit(2);
+        }
+        // status, history and stats return their output in the result
+        if message["result"]
+            .as_object()
+            .is_some_and(|result| result.len() > 1)
+        {
+            println!("{}", message["result"]);
+        }
+        let failed = message["result"]["failed"].as_bool().unwrap_or(false);
+        std::process::exit(if failed { 1 } else { 0 });
+    }
+    eprintln!("Connection closed before the response");
+    std::process::exit(2);
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("Unix sockets are not supported on this platform");
+    std::process::exit(2);
+}
@@ -1,7 +1,8 @@
 mod executor;
 mod parser;
 
-use crate::data::Credentials;
+use crate::data::{CodeOpen, Credentials};
+pub(crate) use parser::split_commands;
 
 #[derive(Debug, Clone)]
 pub(crate) struct InputCommand {
@@ -34,6 +35,21 @@ impl std::str::FromStr for InputCommand {
     }
 }
 
+/// Quotes a command line argument so that the command parser reads it back as is.
+pub(crate) fn quote_arg(arg: &str) -> String {
+    let (key, value) = match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() && key.bytes().all(|b| b.is_ascii_lowercase()) => {
+            (&arg[..=key.len()], value)
+        }
+        _ => ("", arg),
+    };
+    if !value.is_empty() && !value.contains([' ', '\'', '"']) {
+        return arg.to_string();
+    }
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("{}\"{}\"", key, value)
+}
+
 #[derive(Debug, Clone)]
 pub(crate) enum Command {
     Set(Setting),
@@ -45,6 +61,7 @@ pub(crate) enum Command {
     },
     Build {
         build: Option<String>,
+        force: bool,
     },
     Run {
         cmd: Option<String>,
@@ -53,9 +70,38 @@ pub(crate) enum Command {
     Test {
         cmd: Option<String>,
     },
+    Watch {
+        cmd: Option<String>,
+    },
     Submit {
         lang: Option<String>,
         file: Option<String>,
+        force: bool,
+        wait: bool,
+        open: Option<CodeOpen>,
+    },
+    Status,
+    /// Asks for the username and password and logs in through the login form
+    Login {
+        /// Save the cookies to the credentials file; asked if not given
+        save: Option<bool>,
+    },
+    History {
+        user: Option<String>,
+        count: usize,
+    },
+    FetchSource {
+        target: SourceTarget,
+        out: Option<String>,
+        force: bool,
+    },
+    Bundle {
+        file: Option<String>,
+    },
+    Stats {
+        from: Option<String>,
+        to: Option<String>,
+        days: Option<u64>,
     },
     Help,
     Exit,
@@ -74,6 +120,22 @@ pub(crate) enum Setting {
     Build(String),
     Cmd(String),
     Input(String),
+    Watch(String),
+    Deps(String),
+    Autobuild(bool),
+    Guard(bool),
+    Open(CodeOpen),
+    JsonOutput(bool),
+}
+
+/// Which submission to download the source of
+#[derive(Debug, Clone)]
+pub(crate) enum SourceTarget {
+    Id(String),
+    /// Latest submission on the current problem
+    Last,
+    /// Fastest accepted submission on the current problem
+    Best,
 }
 
 #[derive(Debug)]
@@ -1,11 +1,25 @@
-use super::{Command, CommandExecuteError, Credentials, Setting};
-use crate::data::{ExampleIO, Preset, ProblemId};
+use super::{Command, CommandExecuteError, Credentials, Setting, SourceTarget};
+use crate::data::{
+    parse_date, suggest_languages, verdict_from_class, CodeOpen, ExampleIO, LangMapping, Preset,
+    ProblemId, ProblemKind, ProblemRecord, Shrink, Stats, SubmissionRecord,
+};
 use crate::global_state::GlobalState;
-use crate::infra::console::{report_stderr, report_stdout, Spinner, SubmitProgress, TestProgress};
+use crate::infra::browser::LOGIN_PAGE;
+use crate::infra::bundler;
+use crate::infra::console::{
+    json_output, report_build, report_error, report_history, report_lines, report_notice,
+    report_problem, report_stats, report_stderr, report_stdout, report_submissions,
+    report_tracking_stopped, set_json_output, Spinner, SubmitProgress, TestProgress,
+};
+use crate::infra::files::{expand_glob, hash_files, hash_source, matches_glob};
+use crate::infra::shrink::{minify_whitespace, strip_comments};
+use crate::infra::submission_log::{append_record, read_records};
 use crate::infra::subprocess::{run_interactive, run_silent, run_with_input_timed, Output};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Password};
 use once_cell::sync::Lazy;
 use regex::{Captures, Regex, Replacer};
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 macro_rules! error {
     ($($t: tt)*) => { Err(CommandExecuteError { msg: format!($($t)*) } ) };
@@ -15,6 +29,25 @@ impl Command {
     pub(crate) fn is_exit(&self) -> bool {
         matches!(self, Command::Exit)
     }
+
+    /// Returns why the command cannot be served over JSON-RPC, if it cannot.
+    pub(crate) fn server_unsupported(&self) -> Option<&'static str> {
+        match self {
+            Command::Exit => Some("Use `shutdown` to stop the server"),
+            Command::Watch { .. } => {
+                Some("watch: Runs until Ctrl+C, which cannot be sent to the server")
+            }
+            Command::Set(Setting::JsonOutput(false)) => {
+                Some("set output: The server always uses JSON output")
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Time limit for running the solution locally, from the problem's time limit in seconds
+fn time_limit(time: f64) -> Duration {
+    Duration::from_secs_f64((time * 3.0 + 2.0).min(10.0))
 }
 
 fn substitute_problem(path: &str, problem_id: &ProblemId) -> String {
@@ -45,6 +78,7 @@ fn substitute_problem(path: &str, problem_id: &ProblemId) -> String {
 
 impl GlobalState {
     pub(crate) fn execute(&mut self, command: &Command) -> anyhow::Result<()> {
+        self.failed = false;
         match command {
             Command::Set(setting) => self.set(setting)?,
             Command::Preset { name } => {
@@ -55,15 +89,24 @@ impl GlobalState {
                 self.preset(preset)?;
             }
             Command::Prob { prob } => self.prob(prob)?,
-            Command::Build { build } => {
+            Command::Build { build, force } => {
                 let Some(prob) = self.problem.as_ref().map(|p| &p.id) else {
                     error!("build: Problem not specified")?
                 };
                 let stored_build = self.build.clone();
                 let build = substitute_problem(build.as_ref().unwrap_or(&stored_build), prob);
-                self.build(&build)?;
+                let file = substitute_problem(&self.file, prob);
+                if !force && self.build_hashes.get(&build) == Some(&self.source_hash(&file, prob)) {
+                    report_notice("Build skipped: source unchanged since the last build (use force=true to rebuild)");
+                } else {
+                    self.failed = !self.build(&build, &file)?;
+                }
             }
             Command::Run { cmd, input } => {
+                if !self.ensure_built("run")? {
+                    self.failed = true;
+                    return Ok(());
+                }
                 let Some((prob, time, kind)) =
                     self.problem.as_ref().map(|p| (&p.id, p.time, &p.kind))
                 else {
@@ -77,7 +120,7 @@ impl GlobalState {
                     );
                     for rest_reason in no_run_reasons {
                         reason += ", ";
-                        reason += &rest_reason;
+                        reason += rest_reason;
                     }
                     error!("{}", reason)?
                 }
@@ -90,75 +133,254 @@ impl GlobalState {
                 let stored_input = self.input.clone();
                 let input = input.as_ref().unwrap_or(&stored_input);
                 let input_data = std::fs::read_to_string(input)?;
-                self.run(
-                    &cmd,
-                    &input_data,
-                    Duration::from_secs_f64((time * 3.0 + 2.0).min(10.0)),
-                )?;
+                let passed = self.run(&cmd, &input_data, time_limit(time))?;
+                self.failed = !passed;
             }
             Command::Test { cmd } => {
-                let Some((prob, time, kind, io)) = self
-                    .problem
-                    .as_ref()
-                    .map(|p| (&p.id, p.time, &p.kind, &p.io))
-                else {
+                if !self.ensure_built("test")? {
+                    self.failed = true;
+                    return Ok(());
+                }
+                let Some(prob) = self.problem.as_ref().map(|p| &p.id) else {
                     error!("test: Problem not specified")?
                 };
-                let mut no_test_reasons = kind.iter().flat_map(|kind| kind.no_test());
-                if let Some(first_reason) = no_test_reasons.next() {
-                    let mut reason = format!(
-                        "test: Current problem does not support test. Reason: {}",
-                        first_reason
-                    );
-                    for rest_reason in no_test_reasons {
-                        reason += ", ";
-                        reason += &rest_reason;
-                    }
-                    error!("{}", reason)?
-                }
-                let mut no_diff_reasons = kind.iter().flat_map(|kind| kind.no_diff());
-                let mut diff = true;
-                if let Some(first_reason) = no_diff_reasons.next() {
-                    let mut reason = format!(
-                        "test: Current problem does not support diff on test output. Reason: {}",
-                        first_reason
-                    );
-                    for rest_reason in no_diff_reasons {
-                        reason += ", ";
-                        reason += &rest_reason;
-                    }
-                    println!("{}", reason);
-                    diff = false;
-                }
                 let stored_cmd = self.cmd.clone();
                 let cmd = substitute_problem(cmd.as_ref().unwrap_or(&stored_cmd), prob);
-                self.test(
-                    &cmd,
-                    io,
-                    Duration::from_secs_f64((time * 3.0 + 2.0).min(10.0)),
-                    diff,
-                )?;
-            }
-            Command::Submit { lang, file } => {
+                let passed = self.sample_test("test", &cmd)?;
+                self.failed = !passed;
+            }
+            Command::Watch { cmd } => {
                 let Some(prob) = self.problem.as_ref().map(|p| &p.id) else {
+                    error!("watch: Problem not specified")?
+                };
+                let mut paths = vec![substitute_problem(&self.file, prob)];
+                paths.extend(
+                    self.watch
+                        .split_whitespace()
+                        .map(|path| substitute_problem(path, prob)),
+                );
+                for pattern in self.deps.split_whitespace() {
+                    let deps = expand_glob(&substitute_problem(pattern, prob));
+                    paths.extend(deps.iter().map(|path| path.display().to_string()));
+                }
+                let build = substitute_problem(&self.build, prob);
+                let file = paths[0].clone();
+                self.watch(&paths, &build, &file, cmd)?;
+            }
+            Command::Submit {
+                lang,
+                file,
+                force,
+                wait,
+                open,
+            } => {
+                let Some(prob) = self.problem.as_ref().map(|p| p.id.clone()) else {
                     error!("submit: Problem not specified")?
                 };
+                let file_arg = file;
+                let file = if let Some(file) = file_arg {
+                    file.clone()
+                } else if !self.file.is_empty() {
+                    self.file.clone()
+                } else {
+                    error!("submit: Solution file not specified")?
+                };
+                let file = substitute_problem(&file, &prob);
+                let mapped_lang = file_arg.as_ref().and_then(|_| self.mapped_lang(&file));
                 let lang = if let Some(lang) = lang {
-                    lang.clone()
+                    self.resolve_lang("submit", lang)?
+                } else if let Some(mapped_lang) = mapped_lang {
+                    report_notice(&format!(
+                        "submit: Using language {} for {}",
+                        mapped_lang, file
+                    ));
+                    self.resolve_lang("submit", &mapped_lang)?
                 } else if !self.lang.is_empty() {
                     self.lang.clone()
                 } else {
                     error!("submit: Language not specified")?
                 };
+                let ready = if self.guard && !force {
+                    match self.guard(&file)? {
+                        Some(reason) => {
+                            report_error(&format!("submit: Blocked by guard: {}", reason));
+                            false
+                        }
+                        None => true,
+                    }
+                } else {
+                    self.ensure_built("submit")?
+                };
+                if !ready {
+                    self.failed = true;
+                    return Ok(());
+                }
+                let open = open.or(self.open);
+                // the process would end before the verdict in batch mode
+                let wait = *wait || self.batch;
+                let verdict = self.submit(&lang, &file, *force, wait, open)?;
+                self.failed = wait && verdict.as_deref() != Some("AC");
+            }
+            Command::Status => {
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)?
+                    .as_secs();
+                let submissions = self
+                    .tracker
+                    .get()
+                    .map(|tracker| tracker.submissions())
+                    .unwrap_or_default();
+                let records = submissions
+                    .iter()
+                    .rev()
+                    .take(STATUS_LIMIT)
+                    .map(|s| (&s.record, now.saturating_sub(s.record.timestamp)))
+                    .collect::<Vec<_>>();
+                report_submissions(&records);
+            }
+            Command::Login { save } => {
+                // stdin carries the requests in the JSON-RPC server
+                if json_output() {
+                    error!("login: Not available in JSON mode; use `set credentials` instead")?
+                }
+                self.login_interactive(*save)?;
+            }
+            Command::History { user, count } => {
+                let Some(prob) = self.problem.as_ref().map(|p| &p.id) else {
+                    error!("history: Problem not specified")?
+                };
+                let spinner = Spinner::new("Loading submission history...");
+                let username = match user {
+                    Some(user) => Some(user.clone()),
+                    None => self.browser()?.get_username()?,
+                };
+                let Some(username) = username else {
+                    spinner.abandon("Not logged in");
+                    error!("history: Login is required unless user= is given")?
+                };
+                let rows = match self.browser()?.get_submissions(prob, &username, *count) {
+                    Ok(rows) => rows,
+                    Err(err) => {
+                        spinner.abandon("Failed to load submission history");
+                        return Err(err);
+                    }
+                };
+                spinner.finish(&format!("Submissions of {} on {}", username, prob));
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)?
+                    .as_secs();
+                report_history(&rows, now);
+            }
+            Command::FetchSource { target, out, force } => {
+                let prob = self.problem.as_ref().map(|p| p.id.clone());
+                let submission_id = match target {
+                    SourceTarget::Id(id) => id.clone(),
+                    SourceTarget::Last | SourceTarget::Best => {
+                        let Some(prob) = &prob else {
+                            error!("fetchsource: Problem not specified")?
+                        };
+                        self.find_submission(prob, matches!(target, SourceTarget::Best))?
+                    }
+                };
+                let out = if let Some(out) = out {
+                    out.clone()
+                } else if let (false, Some(prob)) = (self.file.is_empty(), &prob) {
+                    substitute_problem(&self.file, prob)
+                } else {
+                    error!("fetchsource: Output file not specified")?
+                };
+
+                let spinner = Spinner::new(&format!("Downloading source of #{}...", submission_id));
+                let source = match self.browser()?.get_source(&submission_id) {
+                    Ok(source) => source,
+                    Err(err) => {
+                        spinner.abandon("Download failed");
+                        return Err(err);
+                    }
+                };
+                spinner.finish(&format!("Downloaded source of #{}", submission_id));
+                let out_path = Path::new(&out);
+                match std::fs::read_to_string(out_path) {
+                    Ok(existing) if existing == source => {
+                        report_notice(&format!("{} is already up to date", out));
+                        return Ok(());
+                    }
+                    Ok(_) if *force => {}
+                    Ok(_) => {
+                        // stdin carries the requests in the JSON-RPC server
+                        if json_output() {
+                            error!(
+                                "fetchsource: {} already exists; use force=true to overwrite it or out= to write elsewhere",
+                                out
+                            )?
+                        }
+                        let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+                            .with_prompt(format!("Overwrite {}?", out))
+                            .default(false)
+                            .interact()?;
+                        if !confirmed {
+                            error!("fetchsource: Cancelled")?
+                        }
+                    }
+                    Err(_) if out_path.exists() => {
+                        error!("fetchsource: Cannot overwrite `{}`", out)?
+                    }
+                    Err(_) => {}
+                }
+                if let Some(dir) = out_path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(out_path, source)?;
+                report_notice(&format!("Source of #{} written to {}", submission_id, out));
+            }
+            Command::Bundle { file } => {
+                let Some(prob) = self.problem.as_ref().map(|p| &p.id) else {
+                    error!("bundle: Problem not specified")?
+                };
                 let file = if let Some(file) = file {
                     file.clone()
                 } else if !self.file.is_empty() {
                     self.file.clone()
                 } else {
-                    error!("submit: Solution file not specified")?
+                    error!("bundle: Solution file not specified")?
                 };
                 let file = substitute_problem(&file, prob);
-                self.submit(&lang, &file)?;
+                let Ok(source) = std::fs::read_to_string(&file) else {
+                    error!("bundle: File `{}` does not exist", file)?
+                };
+                if self.bundle(&file, &source)?.is_none() {
+                    report_notice(&format!("bundle: Nothing to bundle for {}", file));
+                }
+            }
+            Command::Stats { from, to, days } => {
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)?
+                    .as_secs();
+                let from = match (from, days) {
+                    (Some(_), Some(_)) => error!("stats: from= and days= cannot be used together")?,
+                    (Some(from), None) => {
+                        let Some(from) = parse_date(from) else {
+                            error!("stats: Invalid date `{}`; expected YYYY-MM-DD", from)?
+                        };
+                        from
+                    }
+                    (None, Some(days)) => now.saturating_sub(days * 86400),
+                    (None, None) if to.is_some() => 0,
+                    (None, None) => self.session_start,
+                };
+                let to = match to {
+                    Some(to) => {
+                        let Some(to) = parse_date(to) else {
+                            error!("stats: Invalid date `{}`; expected YYYY-MM-DD", to)?
+                        };
+                        // the end date is inclusive
+                        to + 86400
+                    }
+                    None => u64::MAX,
+                };
+                let problems = read_records::<ProblemRecord>(&self.problem_log())?;
+                let submissions = read_records::<SubmissionRecord>(&self.submission_log)?;
+                report_stats(&Stats::new(&problems, &submissions, from, to));
             }
             Command::Help => {
                 self.help()?;
@@ -171,10 +393,10 @@ impl GlobalState {
                 println!("{:?}", self.problem_cache);
             }
             Command::DebugScreenshot => {
-                self.browser.screenshot()?;
+                self.browser()?.screenshot()?;
             }
             Command::DebugSource => {
-                let source = self.browser.source()?;
+                let source = self.browser()?.source()?;
                 std::fs::write("./source", &source)?;
             }
         }
@@ -186,23 +408,28 @@ impl GlobalState {
             Setting::Credentials(Credentials {
                 bojautologin,
                 onlinejudge,
+                username,
             }) => {
-                self.credentials.bojautologin.clear();
-                self.credentials.bojautologin += bojautologin;
-                self.credentials.onlinejudge.clear();
-                self.credentials.onlinejudge += onlinejudge;
-
-                let spinner = Spinner::new("Logging in...");
-                self.browser.login(bojautologin, onlinejudge)?;
-                if let Some(username) = self.browser.get_username()? {
-                    spinner.finish(&format!("Logged in as {}", username));
-                } else {
-                    spinner.abandon("Login failed with the credentials provided");
+                let credentials = self.credentials.get_mut();
+                credentials.bojautologin.clear();
+                credentials.bojautologin += bojautologin;
+                credentials.onlinejudge.clear();
+                credentials.onlinejudge += onlinejudge;
+                credentials.username.clone_from(username);
+                // the language list may have failed for not being logged in
+                self.languages_unavailable = false;
+
+                if let Some(browser) = self.browser.get() {
+                    self.login(browser)?;
+                } else if !self.batch {
+                    // logs in as the browser starts
+                    self.browser()?;
                 }
             }
             Setting::Lang(lang) => {
+                let lang = self.resolve_lang("set lang", lang)?;
                 self.lang.clear();
-                self.lang += lang;
+                self.lang += &lang;
             }
             Setting::File(file) => {
                 self.file.clear();
@@ -225,6 +452,26 @@ impl GlobalState {
                 self.input.clear();
                 self.input += input;
             }
+            Setting::Watch(watch) => {
+                self.watch.clear();
+                self.watch += watch;
+            }
+            Setting::Deps(deps) => {
+                self.deps.clear();
+                self.deps += deps;
+            }
+            Setting::Autobuild(autobuild) => {
+                self.autobuild = *autobuild;
+            }
+            Setting::Guard(guard) => {
+                self.guard = *guard;
+            }
+            Setting::Open(open) => {
+                self.open = Some(*open);
+            }
+            Setting::JsonOutput(json) => {
+                set_json_output(*json);
+            }
         }
         Ok(())
     }
@@ -238,6 +485,11 @@ impl GlobalState {
             build,
             cmd,
             input,
+            watch,
+            deps,
+            autobuild,
+            guard,
+            open,
             ..
         } = preset;
         if let Some(credentials) = credentials {
@@ -261,6 +513,21 @@ impl GlobalState {
         if let Some(input) = input {
             self.set(&Setting::Input(input))?;
         }
+        if let Some(watch) = watch {
+            self.set(&Setting::Watch(watch))?;
+        }
+        if let Some(deps) = deps {
+            self.set(&Setting::Deps(deps))?;
+        }
+        if let Some(autobuild) = autobuild {
+            self.set(&Setting::Autobuild(autobuild))?;
+        }
+        if let Some(guard) = guard {
+            self.set(&Setting::Guard(guard))?;
+        }
+        if let Some(open) = open {
+            self.set(&Setting::Open(open))?;
+        }
         Ok(())
     }
 
@@ -272,32 +539,73 @@ impl GlobalState {
         } else {
             // store the fetched problem to the cache
             let spinner = Spinner::new("Fetching problem...");
-            self.problem = Some(self.browser.get_problem(&problem_id)?);
+            self.problem = Some(self.browser()?.get_problem(&problem_id)?);
             spinner.finish("Fetching done");
             self.problem_cache
                 .insert(problem_id, self.problem.clone().unwrap());
         }
         let problem = self.problem.as_ref().unwrap();
-        println!("Problem {} {}", problem.id, problem.title);
-        println!(
-            "Time limit: {:.3}s{} / Memory limit: {}MB{}",
-            problem.time,
-            if !problem.time_bonus {
-                " (No bonus)"
-            } else {
-                ""
-            },
-            problem.memory,
-            if !problem.memory_bonus {
-                " (No bonus)"
-            } else {
-                ""
-            }
-        );
-        self.init()?;
+        let record = ProblemRecord {
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)?
+                .as_secs(),
+            problem: problem.id.to_string(),
+            title: problem.title.clone(),
+        };
+        if let Err(err) = append_record(&self.problem_log(), &record) {
+            report_notice(&format!("prob: Failed to write the problem log: {}", err));
+        }
+        report_problem(problem);
+        if !self.switch_preset()? {
+            self.init()?;
+        }
         Ok(())
     }
 
+    /// If <auto_preset> is on and the current solution file does not exist for the loaded problem,
+    /// applies the only preset whose solution file exists.
+    /// Returns true if the applied preset already ran its init.
+    fn switch_preset(&mut self) -> anyhow::Result<bool> {
+        let Some(prob) = self.problem.as_ref().map(|p| p.id.clone()) else {
+            return Ok(false);
+        };
+        if !self.auto_preset || Path::new(&substitute_problem(&self.file, &prob)).exists() {
+            return Ok(false);
+        }
+        let candidates = self
+            .preset_order
+            .iter()
+            .filter_map(|name| self.presets.get(name))
+            .filter(|preset| {
+                preset
+                    .file
+                    .as_ref()
+                    .is_some_and(|file| Path::new(&substitute_problem(file, &prob)).exists())
+            })
+            .collect::<Vec<_>>();
+        match &candidates[..] {
+            [] => Ok(false),
+            [preset] => {
+                let preset = (*preset).clone();
+                report_notice(&format!("prob: Switching to preset {}", preset.name));
+                let runs_init = preset.init.is_some();
+                self.preset(preset)?;
+                Ok(runs_init)
+            }
+            _ => {
+                let names = candidates
+                    .iter()
+                    .map(|preset| &preset.name[..])
+                    .collect::<Vec<_>>();
+                report_notice(&format!(
+                    "prob: Solution files exist for multiple presets ({}); keeping the current settings",
+                    names.join(", ")
+                ));
+                Ok(false)
+            }
+        }
+    }
+
     fn init(&self) -> anyhow::Result<()> {
         // if init is empty, do nothing
         if self.init.is_empty() {
@@ -319,19 +627,72 @@ impl GlobalState {
         Ok(())
     }
 
-    fn build(&self, build: &str) -> anyhow::Result<()> {
+    /// Hashes the solution file and the files matched by <deps>.
+    fn source_hash(&self, file: &str, prob: &ProblemId) -> u64 {
+        let mut paths = vec![PathBuf::from(file)];
+        for pattern in self.deps.split_whitespace() {
+            paths.extend(expand_glob(&substitute_problem(pattern, prob)));
+        }
+        hash_files(&paths)
+    }
+
+    /// Returns true if build succeeded.
+    /// On success, the hash of `file` and <deps> taken right before the build is recorded for the build command.
+    fn build(&mut self, build: &str, file: &str) -> anyhow::Result<bool> {
+        let hash = self.problem.as_ref().map(|p| self.source_hash(file, &p.id));
         let spinner = Spinner::new("Running build...");
         let res = run_silent(build)?;
         if let Some(err) = res {
             spinner.abandon("Build returned nonzero exit code");
-            report_stderr(&err);
+            report_build(build, Some(&err));
+            Ok(false)
         } else {
             spinner.finish("Build finished");
+            report_build(build, None);
+            if let Some(hash) = hash {
+                self.build_hashes.insert(build.to_string(), hash);
+                if let Err(err) = self.save_build_hashes() {
+                    report_notice(&format!("build: Failed to save the build hashes: {}", err));
+                }
+            }
+            Ok(true)
         }
-        Ok(())
     }
 
-    fn run(&self, cmd: &str, input: &str, time: Duration) -> anyhow::Result<()> {
+    /// Checks if the source changed since the last successful build.
+    /// If so, rebuilds when <autobuild> is on, and warns otherwise. Returns false if the rebuild failed.
+    fn ensure_built(&mut self, cmd_name: &str) -> anyhow::Result<bool> {
+        if self.build.is_empty() {
+            return Ok(true);
+        }
+        let Some(prob) = self.problem.as_ref().map(|p| p.id.clone()) else {
+            return Ok(true);
+        };
+        let build = substitute_problem(&self.build, &prob);
+        let file = substitute_problem(&self.file, &prob);
+        if self.build_hashes.get(&build) == Some(&self.source_hash(&file, &prob)) {
+            return Ok(true);
+        }
+        if self.autobuild {
+            report_notice(&format!(
+                "{}: Source changed since the last build; rebuilding",
+                cmd_name
+            ));
+            if !self.build(&build, &file)? {
+                report_error(&format!("{}: Build failed", cmd_name));
+                return Ok(false);
+            }
+        } else {
+            report_notice(&format!(
+                "{}: Warning: Source changed since the last build; the binary may be out of date",
+                cmd_name
+            ));
+        }
+        Ok(true)
+    }
+
+    /// Returns false if the run did not finish in time or returned nonzero exit code
+    fn run(&self, cmd: &str, input: &str, time: Duration) -> anyhow::Result<bool> {
         let spinner = Spinner::new("Running code...");
         let Some(Output {
             stdout,
@@ -341,7 +702,7 @@ impl GlobalState {
         }) = run_with_input_timed(cmd, input, time)?
         else {
             spinner.abandon(&format!("Run did not finish in {:.3}s", time.as_secs_f64()));
-            return Ok(());
+            return Ok(false);
         };
         let duration = duration.as_secs_f64();
         if !success {
@@ -356,77 +717,642 @@ impl GlobalState {
         if !stderr.is_empty() {
             report_stderr(&stderr);
         }
-        Ok(())
+        Ok(success)
     }
 
-    fn test(&self, cmd: &str, io: &[ExampleIO], time: Duration, diff: bool) -> anyhow::Result<()> {
+    /// Returns true if all sample tests passed
+    fn test(
+        &self,
+        cmd: &str,
+        io: &[ExampleIO],
+        time: Duration,
+        diff: bool,
+    ) -> anyhow::Result<bool> {
         let io_count = io.len();
         let test_progress = TestProgress::new(io_count as u64);
         for ExampleIO { input, output } in io {
             let expected = output;
             let output = run_with_input_timed(cmd, input, time)?;
             if !test_progress.handle_test_result(input, expected, output, diff) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs the sample tests of the current problem with the given command.
+    /// Returns true if all sample tests passed.
+    fn sample_test(&self, cmd_name: &str, cmd: &str) -> anyhow::Result<bool> {
+        let Some((time, kind, io)) = self.problem.as_ref().map(|p| (p.time, &p.kind, &p.io)) else {
+            error!("{}: Problem not specified", cmd_name)?
+        };
+        let mut no_test_reasons = kind.iter().flat_map(|kind| kind.no_test());
+        if let Some(first_reason) = no_test_reasons.next() {
+            let mut reason = format!(
+                "{}: Current problem does not support test. Reason: {}",
+                cmd_name, first_reason
+            );
+            for rest_reason in no_test_reasons {
+                reason += ", ";
+                reason += rest_reason;
+            }
+            error!("{}", reason)?
+        }
+        let mut no_diff_reasons = kind.iter().flat_map(|kind| kind.no_diff());
+        let mut diff = true;
+        if let Some(first_reason) = no_diff_reasons.next() {
+            let mut reason = format!(
+                "{}: Current problem does not support diff on test output. Reason: {}",
+                cmd_name, first_reason
+            );
+            for rest_reason in no_diff_reasons {
+                reason += ", ";
+                reason += rest_reason;
+            }
+            report_notice(&reason);
+            diff = false;
+        }
+        self.test(cmd, io, time_limit(time), diff)
+    }
+
+    /// Builds the file to submit and runs the sample tests on it before submission.
+    /// Returns why the submission is blocked if the build fails or any sample test fails, and fails if no preset can build and run the file.
+    /// For problems without output diff (e.g. special judge), only RE and TLE count as failures.
+    fn guard(&mut self, file: &str) -> anyhow::Result<Option<&'static str>> {
+        let Some(problem) = self.problem.clone() else {
+            return Ok(None);
+        };
+        if let Some(reason) = problem.kind.iter().find_map(|kind| kind.no_test()) {
+            report_notice(&format!(
+                "submit: Guard skipped sample tests. Reason: {}",
+                reason
+            ));
+            return Ok(None);
+        }
+        let Some((build, cmd)) = self.commands_for(file, &problem.id) else {
+            error!(
+                "submit: Blocked by guard: No preset builds and runs `{}` (use force=true to submit anyway)",
+                file
+            )?
+        };
+        let build = substitute_problem(&build, &problem.id);
+        if !build.is_empty()
+            && self.build_hashes.get(&build) != Some(&self.source_hash(file, &problem.id))
+            && !self.build(&build, file)?
+        {
+            return Ok(Some("Build failed"));
+        }
+        let cmd = substitute_problem(&cmd, &problem.id);
+        if !self.sample_test("submit", &cmd)? {
+            return Ok(Some("Sample test failed (use force=true to submit anyway)"));
+        }
+        Ok(None)
+    }
+
+    /// Finds the build and run commands for a solution file: the current ones for the current solution file,
+    /// or else those of the preset with that file, or of the preset it is mapped to in <lang_map>.
+    fn commands_for(&self, file: &str, prob: &ProblemId) -> Option<(String, String)> {
+        if substitute_problem(&self.file, prob) == file {
+            return Some((self.build.clone(), self.cmd.clone()));
+        }
+        let preset = self
+            .preset_order
+            .iter()
+            .filter_map(|name| self.presets.get(name))
+            .find(|preset| {
+                preset
+                    .file
+                    .as_ref()
+                    .is_some_and(|preset_file| substitute_problem(preset_file, prob) == file)
+            })
+            .or_else(|| {
+                self.lang_mappings(file)
+                    .find_map(|mapping| self.presets.get(mapping.preset.as_ref()?))
+            })?;
+        Some((
+            preset.build.clone().unwrap_or_default(),
+            preset.cmd.clone()?,
+        ))
+    }
+
+    fn watch(
+        &mut self,
+        paths: &[String],
+        build: &str,
+        file: &str,
+        cmd: &Option<String>,
+    ) -> anyhow::Result<()> {
+        fn modified_times(paths: &[String]) -> Vec<Option<SystemTime>> {
+            paths
+                .iter()
+                .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+                .collect()
+        }
+        let term = console::Term::stdout();
+        let mut last_modified = None;
+        loop {
+            if self.ctrlc_channel.try_recv().is_ok() {
+                self.ctrlc_channel.try_iter().count();
                 break;
             }
+            let modified = modified_times(paths);
+            if last_modified.as_ref() != Some(&modified) {
+                last_modified = Some(modified);
+                term.clear_screen()?;
+                report_notice(&format!(
+                    "Watching {}. Press Ctrl+C to stop watching.",
+                    paths.join(", ")
+                ));
+                // nothing to build for interpreted languages
+                if build.is_empty() || self.build(build, file)? {
+                    let test = Command::Test { cmd: cmd.clone() };
+                    if let Err(e) = self.execute(&test) {
+                        report_error(&e);
+                    }
+                }
+            }
+            std::thread::sleep(Duration::from_millis(300));
         }
         Ok(())
     }
 
-    fn submit(&self, lang: &str, file: &str) -> anyhow::Result<()> {
+    /// Submits the solution and leaves the judging to the tracker.
+    /// With `wait`, the progress is shown until the verdict or Ctrl+C, and the verdict is returned.
+    fn submit(
+        &self,
+        lang: &str,
+        file: &str,
+        force: bool,
+        wait: bool,
+        open: Option<CodeOpen>,
+    ) -> anyhow::Result<Option<String>> {
         let Some(prob) = self.problem.as_ref().map(|p| &p.id) else {
             error!("submit: Problem not specified")?
         };
         let Ok(source) = std::fs::read_to_string(file) else {
             error!("submit: File `{}` does not exist", file)?
         };
+        let source = self.bundle(file, &source)?.unwrap_or(source);
+        let source = self.check_source(file, source, force)?;
+        for kind in self.problem.iter().flat_map(|p| &p.kind) {
+            match kind {
+                ProblemKind::SubmitLimit(limit) => self.check_submit_limit(prob, *limit, force)?,
+                ProblemKind::LanguageRestrict => self.check_allowed_language(prob, lang)?,
+                _ => {}
+            }
+        }
 
         let spinner = Spinner::new("Submitting code...");
-        self.browser.submit_solution(prob, &source, lang)?;
-        spinner.finish("Code submitted. Press Ctrl+C to stop watching submission status.");
+        let lang_id = self
+            .languages
+            .iter()
+            .flatten()
+            .find(|language| language.name == lang)
+            .map(|language| &language.id[..]);
+        let submission_id = self
+            .browser()?
+            .submit_solution(prob, &source, lang, lang_id, open)?;
+        spinner.finish(&format!(
+            "Code submitted as #{}",
+            submission_id.as_deref().unwrap_or("?")
+        ));
 
+        let record = SubmissionRecord {
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)?
+                .as_secs(),
+            problem: prob.to_string(),
+            title: self
+                .problem
+                .as_ref()
+                .map(|p| p.title.clone())
+                .unwrap_or_default(),
+            lang: lang.to_string(),
+            file: file.to_string(),
+            source_hash: hash_source(&source),
+            submission_id,
+            verdict: None,
+            status: "Waiting for response...".to_string(),
+            time: None,
+            memory: None,
+            score: None,
+            source,
+        };
+        let index = self
+            .tracker()?
+            .track(record, self.submission_log.clone(), wait);
+        if !wait {
+            report_notice("Judging in background; use `status` to check progress.");
+            return Ok(None);
+        }
+
+        report_notice("Press Ctrl+C to stop watching submission status.");
         let submit_progress = SubmitProgress::new();
         loop {
-            let (status_text, status_class) = self.browser.get_submission_status()?;
-            if submit_progress.update(&status_text, &status_class) {
-                break;
+            let submission = self.tracker()?.get(index);
+            if submit_progress.update(&submission.record.status, &submission.status_class) {
+                return Ok(submission.record.verdict);
+            }
+            if let Some(reason) = &submission.stopped {
+                drop(submit_progress);
+                report_tracking_stopped(&submission.record, reason);
+                return Ok(None);
             }
             if self.ctrlc_channel.try_recv().is_ok() {
                 self.ctrlc_channel.try_iter().count();
+                self.tracker()?.unwatch(index);
+                drop(submit_progress);
+                if !self.batch {
+                    report_notice(
+                        "Judging continues in background; use `status` to check progress.",
+                    );
+                }
+                return Ok(None);
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Bundles the solution if boj.toml has a `[bundle]` section that applies to the file,
+    /// and writes the result to the bundle output path.
+    fn bundle(&self, file: &str, source: &str) -> anyhow::Result<Option<String>> {
+        let (Some(config), Some(prob)) = (self.bundle.as_ref(), self.problem.as_ref()) else {
+            return Ok(None);
+        };
+        let spinner = Spinner::new("Bundling source...");
+        let bundled = match bundler::bundle(Path::new(file), source, config) {
+            Ok(Some(bundled)) => bundled,
+            Ok(None) => return Ok(None),
+            Err(err) => {
+                spinner.abandon("Bundling failed");
+                return Err(err);
+            }
+        };
+        let output = match &config.output {
+            Some(output) => substitute_problem(output, &prob.id),
+            None => {
+                let ext = Path::new(file).extension().unwrap_or_default();
+                format!("bundled.{}", ext.to_string_lossy())
+            }
+        };
+        if let Some(dir) = Path::new(&output).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&output, &bundled)?;
+        spinner.finish(&format!("Bundled source written to {}", output));
+        Ok(Some(bundled))
+    }
+
+    /// Runs the checks in boj.toml on the source to submit, and returns the possibly shrunk source.
+    /// Denied patterns block the submission unless forced.
+    fn check_source(&self, file: &str, source: String, force: bool) -> anyhow::Result<String> {
+        let Some(config) = self.check.as_ref() else {
+            return Ok(source);
+        };
+        let mut denied = vec![];
+        for pattern in &config.deny {
+            let Ok(re) = Regex::new(pattern) else {
+                error!("submit: Invalid deny pattern `{}` in boj.toml", pattern)?
+            };
+            denied.extend(
+                source
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| re.is_match(line))
+                    .map(|(lineno, line)| (lineno + 1, line)),
+            );
+        }
+        if !denied.is_empty() {
+            denied.sort();
+            denied.dedup();
+            report_lines("Denied patterns found:", &denied);
+            if !force {
+                error!("submit: Source contains denied patterns (use force=true to submit anyway)")?
+            }
+        }
+
+        let path = Path::new(file);
+        let original_len = source.len();
+        let mut source = source;
+        for step in &config.shrink {
+            if source.len() <= config.max_length {
                 break;
             }
+            source = match step {
+                Shrink::Comments => strip_comments(path, &source).unwrap_or(source),
+                Shrink::Whitespace => minify_whitespace(path, &source),
+            };
+        }
+        if source.len() < original_len {
+            report_notice(&format!(
+                "submit: Shrunk source from {} to {} bytes",
+                original_len,
+                source.len()
+            ));
+        }
+        if source.len() > config.max_length {
+            report_notice(&format!(
+                "submit: Warning: Source is {} bytes, over the code length limit of {} bytes",
+                source.len(),
+                config.max_length
+            ));
+        }
+        Ok(source)
+    }
+
+    /// Finds the latest submission on the problem, or the accepted one with the least time and memory.
+    fn find_submission(&self, prob: &ProblemId, best: bool) -> anyhow::Result<String> {
+        let spinner = Spinner::new("Looking up submissions...");
+        let Some(username) = self.browser()?.get_username()? else {
+            spinner.abandon("Not logged in");
+            error!("fetchsource: Login is required")?
+        };
+        let limit = if best { usize::MAX } else { 1 };
+        let rows = match self.browser()?.get_submissions(prob, &username, limit) {
+            Ok(rows) => rows,
+            Err(err) => {
+                spinner.abandon("Failed to load submissions");
+                return Err(err);
+            }
+        };
+        let row = if best {
+            rows.iter()
+                .filter(|row| verdict_from_class(&row.status.class).as_deref() == Some("AC"))
+                .min_by_key(|row| {
+                    let key = |value: Option<u64>| value.unwrap_or(u64::MAX);
+                    (
+                        key(row.status.time),
+                        key(row.status.memory),
+                        key(row.length),
+                    )
+                })
+        } else {
+            rows.first()
+        };
+        let Some(row) = row else {
+            spinner.abandon("No submission found");
+            if best {
+                error!("fetchsource: No accepted submission on {}", prob)?
+            } else {
+                error!("fetchsource: No submission on {}", prob)?
+            }
+        };
+        spinner.finish(&format!("Found submission #{}", row.submission_id));
+        Ok(row.submission_id.clone())
+    }
+
+    /// Shows how many submissions are left, and asks for confirmation on the last one unless `force` is set.
+    /// In JSON mode, where nobody can answer, the last one needs `force`.
+    /// If the submissions cannot be counted, only warns about the limit.
+    fn check_submit_limit(
+        &self,
+        prob: &ProblemId,
+        limit: usize,
+        force: bool,
+    ) -> anyhow::Result<()> {
+        let spinner = Spinner::new("Counting previous submissions...");
+        let Some(username) = self.browser()?.get_username()? else {
+            spinner.abandon("Not logged in");
+            error!("submit: Login is required to check the submit limit")?
+        };
+        let count = match self.browser()?.count_submissions(prob, &username) {
+            Ok(Some(count)) => count,
+            Ok(None) => {
+                spinner.abandon("Previous submissions cannot be counted on contest problems");
+                report_notice(&format!(
+                    "submit: Warning: This problem allows only {} submissions",
+                    limit
+                ));
+                return Ok(());
+            }
+            Err(err) => {
+                spinner.abandon("Failed to count previous submissions");
+                report_notice(&format!(
+                    "submit: Warning: This problem allows only {} submissions ({})",
+                    limit, err
+                ));
+                return Ok(());
+            }
+        };
+        let left = limit.saturating_sub(count);
+        if left == 0 {
+            spinner.abandon(&format!("No submissions left (limit: {})", limit));
+            error!("submit: Submit limit reached")?
+        } else {
+            spinner.finish(&format!("{} of {} submissions left", left, limit));
+        }
+        if left == 1 && !force {
+            // stdin carries the requests in the JSON-RPC server
+            if json_output() {
+                error!("submit: This is your last submission; use force=true to submit it")?
+            }
+            let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("This is your last submission. Submit anyway?")
+                .default(false)
+                .interact()?;
+            if !confirmed {
+                error!("submit: Cancelled")?
+            }
         }
         Ok(())
     }
 
+    /// Logs in with the username and password typed in, and offers to save the resulting cookies.
+    fn login_interactive(&mut self, save: Option<bool>) -> anyhow::Result<()> {
+        let theme = ColorfulTheme::default();
+        let username: String = Input::with_theme(&theme)
+            .with_prompt("Username")
+            .interact_text()?;
+        let password = Password::with_theme(&theme)
+            .with_prompt("Password")
+            .interact()?;
+        let login_page = self.webdriver.login_page.as_deref().unwrap_or(LOGIN_PAGE);
+        let spinner = Spinner::new("Logging in...");
+        let result = self
+            .browser()?
+            .login_with_password(login_page, &username, &password)?;
+        let Some(credentials) = result else {
+            spinner.abandon("Login failed with the username and password provided");
+            error!("login: Login failed")?
+        };
+        spinner.finish(&format!("Logged in as {}", username));
+        *self.credentials.get_mut() = credentials;
+        self.languages_unavailable = false;
+
+        if self.credential_store.has_command() {
+            return Ok(());
+        }
+        let file = self.credential_store.file();
+        let save = match save {
+            Some(save) => save,
+            None => Confirm::with_theme(&theme)
+                .with_prompt(format!("Save the login cookies to {}?", file.display()))
+                .default(true)
+                .interact()?,
+        };
+        if save {
+            self.credential_store.save(self.credentials.get_mut())?;
+            report_notice(&format!("Login cookies saved to {}", file.display()));
+        }
+        Ok(())
+    }
+
+    fn check_allowed_language(&self, prob: &ProblemId, lang: &str) -> anyhow::Result<()> {
+        let spinner = Spinner::new("Fetching allowed languages...");
+        let allowed = self.browser()?.get_languages(prob)?;
+        if allowed.iter().any(|allowed| allowed.name == lang) {
+            spinner.finish(&format!("Language {} is allowed", lang));
+        } else {
+            spinner.abandon(&format!("Language {} is not allowed", lang));
+            error!(
+                "submit: This problem only allows the following languages: {}",
+                allowed
+                    .iter()
+                    .map(|allowed| &allowed.name[..])
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?
+        }
+        Ok(())
+    }
+
+    /// Resolves an alias from boj.toml and checks the language against the language list.
+    /// The list is fetched from the submit page on first use.
+    /// If the list cannot be fetched (e.g. not logged in yet), the language is accepted as is
+    /// for the rest of the session.
+    fn resolve_lang(&mut self, cmd_name: &str, lang: &str) -> anyhow::Result<String> {
+        let lang = self
+            .lang_aliases
+            .get(lang)
+            .cloned()
+            .unwrap_or_else(|| lang.to_string());
+        if self.languages.is_none() {
+            if self.languages_unavailable || self.browser.get().is_none() {
+                // not worth starting the browser for; the submit page checks the language anyway
+                return Ok(lang);
+            }
+            let spinner = Spinner::new("Fetching language list...");
+            let any_problem = ProblemId::Problem("1000".to_string());
+            match self.browser()?.get_languages(&any_problem) {
+                Ok(languages) if !languages.is_empty() => {
+                    spinner.finish(&format!("Fetched {} languages", languages.len()));
+                    self.languages = Some(languages);
+                }
+                _ => {
+                    spinner.abandon("Could not fetch language list; skipping validation");
+                    self.languages_unavailable = true;
+                    return Ok(lang);
+                }
+            }
+        }
+        let languages = self.languages.as_deref().unwrap_or_default();
+        if languages.iter().any(|language| language.name == lang) {
+            return Ok(lang);
+        }
+        let suggestions = suggest_languages(languages, &lang);
+        if suggestions.is_empty() {
+            error!("{}: Unknown language `{}`", cmd_name, lang)?
+        }
+        error!(
+            "{}: Unknown language `{}`. Did you mean: {}?",
+            cmd_name,
+            lang,
+            suggestions.join(", ")
+        )?
+    }
+
+    /// The entries of <lang_map> in boj.toml that match the given file.
+    fn lang_mappings<'a>(&'a self, file: &'a str) -> impl Iterator<Item = &'a LangMapping> {
+        let path = Path::new(file);
+        self.lang_map.iter().filter(move |mapping| {
+            if mapping.pattern.contains(['*', '?']) {
+                matches_glob(&mapping.pattern, path)
+            } else {
+                let ext = mapping.pattern.trim_start_matches('.');
+                path.extension().is_some_and(|path_ext| path_ext == ext)
+            }
+        })
+    }
+
+    /// Finds the submit language for the given file from the <lang_map> in boj.toml.
+    fn mapped_lang(&self, file: &str) -> Option<String> {
+        self.lang_mappings(file).find_map(|mapping| {
+            mapping.lang.clone().or_else(|| {
+                let preset = self.presets.get(mapping.preset.as_ref()?)?;
+                preset.lang.clone()
+            })
+        })
+    }
+
     fn help(&self) -> anyhow::Result<()> {
-        println!("{}", HELP.trim());
+        report_notice(HELP.trim());
         Ok(())
     }
 }
 
+/// Number of recent submissions listed by `status`
+const STATUS_LIMIT: usize = 10;
+
 const HELP: &str = "
 set credentials <bojautologin> <onlinejudge>
-    Set BOJ login cookies and log in with them.
+    Set BOJ login cookies and log in with them. The cookies are masked and left out of the history.
+    They can also be kept in the credentials file or printed by credentials_command (see boj.toml).
+login [save=<on|off>]
+    Log in with your username and password instead of cookies, with 'remember me' checked.
+    The resulting cookies can be saved to the credentials file; save= answers the question in advance.
 set lang <lang>
 set file <file>
 set init <init>
 set build <build>
 set cmd <cmd>
 set input <input>
+set watch <paths>
+set deps <globs>
     Set default value for the given variable.
+set autobuild <on|off>
+    Rebuild before run/test/submit if the solution file or <deps> changed
+    since the last build (on), or just warn about it (off).
+set guard <on|off>
+    Build and run sample tests before submit, and block the submission on failure.
+set output <text|json>
+    Print results as colored text, or as one JSON object per line
+    (problem, build, test, submission_progress, submission_result, ...).
+set open <public|private|ac>
+    Set who can see your submitted code: everyone, only you,
+    or everyone once accepted. The submit page default is used if not set.
 prob <prob>
     Load the problem <prob> and set it as the current problem.
     If <init> is set, run it.
-build [build]
-    Build your solution.
+build [build] [force=true]
+    Build your solution. Skipped if the source is unchanged since the last build.
 run [i=input] [c=cmd]
     Run your solution with a custom input file.
 test [c=cmd]
     Test your solution against sample test cases.
-submit [l=lang] [f=file]
-    Submit your solution to BOJ.
+watch [c=cmd]
+    Build and test your solution whenever the solution file
+    or one of the <watch> paths changes.
+bundle [f=file]
+    Bundle your solution and library code into a single file, as done on submit.
+submit [l=lang] [f=file] [force=true] [wait=true] [open=public|private|ac]
+    Submit your solution to BOJ. Judging is watched in the background
+    and the result is printed when it is done.
+    force=true bypasses the guard, the denied patterns in boj.toml and the last-submission prompt.
+    wait=true shows the judging progress until the result.
+    open= overrides the code visibility set by `set open`.
+status
+    List the submissions in this session with their current status.
+history [user=id] [n=20]
+    List the latest n submissions of yours (or the given user) on the current problem.
+fetchsource <submission-id>|last|best [out=file] [force=true]
+    Download a submitted source to the solution file or the given file.
+    force=true overwrites an existing file without asking.
+    last and best pick your latest and fastest accepted submission on the current problem.
 preset <name>
     Apply one of the presets defined in boj.toml.
+stats [from=YYYY-MM-DD] [to=YYYY-MM-DD] [days=n]
+    Summarize the problems and submissions in this session,
+    or in the given date range (UTC) or the last n days.
 help
     Display this help.
 exit
@@ -435,5 +1361,6 @@ $ <shell cmd>
     Run an arbitrary shell command.
 ^C
     During build/run/test, kill the running program.
-    During submit, stop watching the submission status.
+    During watch, stop watching the files.
+    During submit wait=true, stop watching; judging is still tracked in the background.
 ";
@@ -1,10 +1,30 @@
-use super::{Command, CommandParseError, Credentials, Setting};
+use super::{CodeOpen, Command, CommandParseError, Credentials, Setting, SourceTarget};
 use std::collections::HashMap;
 
 macro_rules! error {
     ($($t: tt)*) => { Err(CommandParseError { msg: format!($($t)*) } ) };
 }
 
+fn parse_switch(cmd: &str, value: &str) -> Result<bool, CommandParseError> {
+    match value {
+        "on" | "true" => Ok(true),
+        "off" | "false" => Ok(false),
+        _ => error!("{}: Expected `on` or `off`, found `{}`", cmd, value),
+    }
+}
+
+fn parse_open(cmd: &str, value: &str) -> Result<CodeOpen, CommandParseError> {
+    match value {
+        "public" => Ok(CodeOpen::Public),
+        "private" => Ok(CodeOpen::Private),
+        "ac" => Ok(CodeOpen::Ac),
+        _ => error!(
+            "{}: Expected `public`, `private` or `ac`, found `{}`",
+            cmd, value
+        ),
+    }
+}
+
 struct RawCommand {
     main_cmd: String,
     shell: bool,
@@ -123,7 +143,7 @@ impl RawCommand {
                 if let Some(equal_pos) = input.iter().position(|&b| b == b'=') {
                     if input[..equal_pos].iter().all(|&b| b.is_ascii_lowercase()) {
                         let kw = String::from_utf8_lossy(&input[..equal_pos]);
-                        input = &input[..equal_pos + 1];
+                        input = &input[equal_pos + 1..];
                         break 'keyword Some(kw.to_string());
                     }
                 }
@@ -146,6 +166,47 @@ impl RawCommand {
     }
 }
 
+/// Splits a script into command lines at `;` and newlines, except inside quoted arguments.
+/// Quotes follow the command parser: they start an argument only right after a space or `=`,
+/// and `\` escapes the next character inside them. Empty lines are dropped.
+pub(crate) fn split_commands(script: &str) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = vec![];
+    let mut quote = None;
+    let mut bytes = script.bytes();
+    while let Some(b) = bytes.next() {
+        match quote {
+            Some(q) => {
+                line.push(b);
+                if b == b'\\' {
+                    line.extend(bytes.next());
+                } else if b == q {
+                    quote = None;
+                }
+            }
+            None if b == b';' || b == b'\n' => {
+                lines.push(std::mem::take(&mut line));
+            }
+            None => {
+                // a quote starts an argument only at the start of the line or after a space or =
+                let at_start = line.iter().all(|&b| b == b' ');
+                if (b == b'\'' || b == b'"')
+                    && (at_start || matches!(line.last(), Some(b' ' | b'=')))
+                {
+                    quote = Some(b);
+                }
+                line.push(b);
+            }
+        }
+    }
+    lines.push(line);
+    lines
+        .into_iter()
+        .map(|line| String::from_utf8_lossy(&line).trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
 impl std::str::FromStr for Command {
     type Err = CommandParseError;
     fn from_str(input: &str) -> Result<Self, CommandParseError> {
@@ -197,9 +258,10 @@ impl std::str::FromStr for Command {
                         Setting::Credentials(Credentials {
                             bojautologin: args[1].clone(),
                             onlinejudge: args[2].clone(),
+                            username: None,
                         })
                     }
-                    "lang" | "file" | "build" | "cmd" | "input" | "init" => {
+                    "lang" | "file" | "build" | "cmd" | "input" | "init" | "watch" | "deps" => {
                         if args.len() == 1 {
                             return error!("set {}: Missing argument <{}>", variable, variable);
                         } else if args.len() > 2 {
@@ -213,9 +275,49 @@ impl std::str::FromStr for Command {
                             "build" => Setting::Build(arg),
                             "cmd" => Setting::Cmd(arg),
                             "input" => Setting::Input(arg),
+                            "watch" => Setting::Watch(arg),
+                            "deps" => Setting::Deps(arg),
+                            _ => unreachable!(),
+                        }
+                    }
+                    "autobuild" | "guard" => {
+                        if args.len() == 1 {
+                            return error!("set {}: Missing argument <on|off>", variable);
+                        } else if args.len() > 2 {
+                            return error!("set {}: Too many arguments", variable);
+                        }
+                        let switch = parse_switch(&format!("set {}", variable), &args[1])?;
+                        match variable {
+                            "autobuild" => Setting::Autobuild(switch),
+                            "guard" => Setting::Guard(switch),
                             _ => unreachable!(),
                         }
                     }
+                    "output" => {
+                        if args.len() == 1 {
+                            return error!("set output: Missing argument <text|json>");
+                        } else if args.len() > 2 {
+                            return error!("set output: Too many arguments");
+                        }
+                        match &args[1][..] {
+                            "text" => Setting::JsonOutput(false),
+                            "json" => Setting::JsonOutput(true),
+                            value => {
+                                return error!(
+                                    "set output: Expected `text` or `json`, found `{}`",
+                                    value
+                                )
+                            }
+                        }
+                    }
+                    "open" => {
+                        if args.len() == 1 {
+                            return error!("set open: Missing argument <public|private|ac>");
+                        } else if args.len() > 2 {
+                            return error!("set open: Too many arguments");
+                        }
+                        Setting::Open(parse_open("set open", &args[1])?)
+                    }
                     _ => {
                         return error!("set: Unrecognized variable `{}`", args[0]);
                     }
@@ -253,15 +355,19 @@ impl std::str::FromStr for Command {
             }
             "build" => {
                 let mut build = None;
+                let mut force = false;
                 if args.len() == 1 {
                     build = Some(args[0].clone());
                 } else if !args.is_empty() {
                     return error!("build: Too many positional arguments");
                 }
+                if let Some(f) = kwargs.remove("force") {
+                    force = parse_switch("build", &f)?;
+                }
                 if !kwargs.is_empty() {
                     return error!("build: Unexpected keyword argument(s)");
                 }
-                Ok(Self::Build { build })
+                Ok(Self::Build { build, force })
             }
             "run" => {
                 let mut cmd = None;
@@ -269,10 +375,10 @@ impl std::str::FromStr for Command {
                 if !args.is_empty() {
                     return error!("run: Unexpected positional argument(s)");
                 }
-                if let Some(c) = kwargs.remove(&"c".to_string()) {
+                if let Some(c) = kwargs.remove("c") {
                     cmd = Some(c);
                 }
-                if let Some(i) = kwargs.remove(&"i".to_string()) {
+                if let Some(i) = kwargs.remove("i") {
                     input = Some(i);
                 }
                 if !kwargs.is_empty() {
@@ -285,7 +391,7 @@ impl std::str::FromStr for Command {
                 if !args.is_empty() {
                     return error!("test: Unexpected positional argument(s)");
                 }
-                if let Some(c) = kwargs.remove(&"c".to_string()) {
+                if let Some(c) = kwargs.remove("c") {
                     cmd = Some(c);
                 }
                 if !kwargs.is_empty() {
@@ -293,22 +399,144 @@ impl std::str::FromStr for Command {
                 }
                 Ok(Self::Test { cmd })
             }
+            "watch" => {
+                let mut cmd = None;
+                if !args.is_empty() {
+                    return error!("watch: Unexpected positional argument(s)");
+                }
+                if let Some(c) = kwargs.remove("c") {
+                    cmd = Some(c);
+                }
+                if !kwargs.is_empty() {
+                    return error!("watch: Unexpected keyword argument(s)");
+                }
+                Ok(Self::Watch { cmd })
+            }
             "submit" => {
                 let mut lang = None;
                 let mut file = None;
+                let mut force = false;
+                let mut wait = false;
+                let mut open = None;
                 if !args.is_empty() {
                     return error!("submit: Unexpected positional argument(s)");
                 }
-                if let Some(l) = kwargs.remove(&"l".to_string()) {
+                if let Some(l) = kwargs.remove("l") {
                     lang = Some(l);
                 }
-                if let Some(f) = kwargs.remove(&"f".to_string()) {
+                if let Some(f) = kwargs.remove("f") {
                     file = Some(f);
                 }
+                if let Some(f) = kwargs.remove("force") {
+                    force = parse_switch("submit", &f)?;
+                }
+                if let Some(w) = kwargs.remove("wait") {
+                    wait = parse_switch("submit", &w)?;
+                }
+                if let Some(o) = kwargs.remove("open") {
+                    open = Some(parse_open("submit", &o)?);
+                }
                 if !kwargs.is_empty() {
                     return error!("submit: Unexpected keyword argument(s)");
                 }
-                Ok(Self::Submit { lang, file })
+                Ok(Self::Submit {
+                    lang,
+                    file,
+                    force,
+                    wait,
+                    open,
+                })
+            }
+            "status" => {
+                if !args.is_empty() || !kwargs.is_empty() {
+                    return error!("status: Unexpected argument(s)");
+                }
+                Ok(Self::Status)
+            }
+            "login" => {
+                if !args.is_empty() {
+                    return error!("login: Unexpected positional argument(s)");
+                }
+                let save = match kwargs.remove("save") {
+                    Some(save) => Some(parse_switch("login", &save)?),
+                    None => None,
+                };
+                if !kwargs.is_empty() {
+                    return error!("login: Unexpected keyword argument(s)");
+                }
+                Ok(Self::Login { save })
+            }
+            "history" => {
+                let mut count = 20;
+                if !args.is_empty() {
+                    return error!("history: Unexpected positional argument(s)");
+                }
+                let user = kwargs.remove("user");
+                if let Some(n) = kwargs.remove("n") {
+                    let Ok(n) = n.parse::<usize>() else {
+                        return error!("history: Invalid number of submissions `{}`", n);
+                    };
+                    count = n;
+                }
+                if !kwargs.is_empty() {
+                    return error!("history: Unexpected keyword argument(s)");
+                }
+                Ok(Self::History { user, count })
+            }
+            "fetchsource" => {
+                if args.is_empty() {
+                    return error!("fetchsource: Missing argument <submission-id>|last|best");
+                } else if args.len() > 1 {
+                    return error!("fetchsource: Too many positional arguments");
+                }
+                let target = match &args[0][..] {
+                    "last" => SourceTarget::Last,
+                    "best" => SourceTarget::Best,
+                    id if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) => {
+                        SourceTarget::Id(id.to_string())
+                    }
+                    arg => return error!("fetchsource: Invalid submission `{}`", arg),
+                };
+                let out = kwargs.remove("out");
+                let mut force = false;
+                if let Some(f) = kwargs.remove("force") {
+                    force = parse_switch("fetchsource", &f)?;
+                }
+                if !kwargs.is_empty() {
+                    return error!("fetchsource: Unexpected keyword argument(s)");
+                }
+                Ok(Self::FetchSource { target, out, force })
+            }
+            "bundle" => {
+                let mut file = None;
+                if !args.is_empty() {
+                    return error!("bundle: Unexpected positional argument(s)");
+                }
+                if let Some(f) = kwargs.remove("f") {
+                    file = Some(f);
+                }
+                if !kwargs.is_empty() {
+                    return error!("bundle: Unexpected keyword argument(s)");
+                }
+                Ok(Self::Bundle { file })
+            }
+            "stats" => {
+                let mut days = None;
+                if !args.is_empty() {
+                    return error!("stats: Unexpected positional argument(s)");
+                }
+                let from = kwargs.remove("from");
+                let to = kwargs.remove("to");
+                if let Some(d) = kwargs.remove("days") {
+                    let Ok(d) = d.parse::<u64>() else {
+                        return error!("stats: Invalid number of days `{}`", d);
+                    };
+                    days = Some(d);
+                }
+                if !kwargs.is_empty() {
+                    return error!("stats: Unexpected keyword argument(s)");
+                }
+                Ok(Self::Stats { from, to, days })
             }
             "exit" => {
                 if !args.is_empty() || !kwargs.is_empty() {
@@ -326,3 +554,119 @@ impl std::str::FromStr for Command {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(input: &str) -> RawCommand {
+        match RawCommand::parse(input) {
+            Ok(raw) => raw,
+            Err(err) => panic!("`{}` failed to parse: {}", input, err.msg),
+        }
+    }
+
+    #[test]
+    fn parse_positional_args() {
+        let cmd = raw("  set lang  Rust ");
+        assert_eq!(cmd.main_cmd, "set");
+        assert!(!cmd.shell);
+        assert_eq!(cmd.args, vec!["lang", "Rust"]);
+        assert!(cmd.kwargs.is_empty());
+    }
+
+    #[test]
+    fn parse_keyword_args() {
+        let cmd = raw("run c=./a.out i=input.txt");
+        assert_eq!(cmd.main_cmd, "run");
+        assert!(cmd.args.is_empty());
+        assert_eq!(cmd.kwargs["c"], "./a.out");
+        assert_eq!(cmd.kwargs["i"], "input.txt");
+        // the value keeps any further `=`
+        let cmd = raw("run c=A=1");
+        assert_eq!(cmd.kwargs["c"], "A=1");
+        // an `=` after a non-keyword is part of a positional argument
+        let cmd = raw("set build X_Y=1");
+        assert_eq!(cmd.args, vec!["build", "X_Y=1"]);
+        assert!(cmd.kwargs.is_empty());
+    }
+
+    #[test]
+    fn parse_mixed_args() {
+        let cmd = raw("submit 1000 f=main.rs wait=true");
+        assert_eq!(cmd.args, vec!["1000"]);
+        assert_eq!(cmd.kwargs["f"], "main.rs");
+        assert_eq!(cmd.kwargs["wait"], "true");
+    }
+
+    #[test]
+    fn parse_command_with_keyword_args() {
+        match "run c=./a.out i=in.txt".parse::<Command>() {
+            Ok(Command::Run { cmd, input }) => {
+                assert_eq!(cmd.as_deref(), Some("./a.out"));
+                assert_eq!(input.as_deref(), Some("in.txt"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!("run x".parse::<Command>().is_err());
+        assert!("run q=1".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn parse_quoted_args() {
+        let cmd = raw(r#"run c='python3 sol.py' i="my input.txt""#);
+        assert_eq!(cmd.kwargs["c"], "python3 sol.py");
+        assert_eq!(cmd.kwargs["i"], "my input.txt");
+        let cmd = raw(r#"set cmd 'echo \'a\' "b" \\'"#);
+        assert_eq!(cmd.args, vec!["cmd", r#"echo 'a' "b" \"#]);
+        let cmd = raw(r#"run c="" i=''"#);
+        assert_eq!(cmd.kwargs["c"], "");
+        assert_eq!(cmd.kwargs["i"], "");
+    }
+
+    #[test]
+    fn parse_quoting_errors() {
+        for input in [
+            "run c='a b",
+            r#"run c="a\""#,
+            r"run c='a\n'",
+            "run c='a'b",
+            "run c=a'b'",
+        ] {
+            assert!(RawCommand::parse(input).is_err(), "`{}` parsed", input);
+        }
+    }
+
+    #[test]
+    fn parse_shell_command() {
+        let cmd = raw("$ echo 'a b' c=d");
+        assert!(cmd.shell);
+        assert_eq!(cmd.main_cmd, "echo 'a b' c=d");
+        assert!(RawCommand::parse("$echo").is_err());
+    }
+
+    #[test]
+    fn split_commands_at_separators() {
+        assert_eq!(
+            split_commands("prob 1000; test\nsubmit ;; "),
+            vec!["prob 1000", "test", "submit"]
+        );
+    }
+
+    #[test]
+    fn split_commands_keeps_quoted_separators() {
+        assert_eq!(
+            split_commands(r#"run c='a; b'; test c="x\"; y"; run 'p;q'"#),
+            vec![r#"run c='a; b'"#, r#"test c="x\"; y""#, "run 'p;q'"]
+        );
+        assert_eq!(
+            split_commands("run c='line\nbreak'; test"),
+            vec!["run c='line\nbreak'", "test"]
+        );
+    }
+
+    #[test]
+    fn split_commands_ignores_quotes_inside_words() {
+        assert_eq!(split_commands("run it's; test"), vec!["run it's", "test"]);
+    }
+}
@@ -1,4 +1,8 @@
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub(crate) enum ProblemId {
     Problem(String),
     ContestProblem(String),
@@ -60,15 +64,27 @@ impl ProblemId {
             Self::ContestProblem(id) => format!("https://www.acmicpc.net/contest/submit/{}", id),
         }
     }
+
+    /// Status page listing the given user's submissions on this problem.
+    /// Not available for contest problems.
+    pub(crate) fn status_url(&self, username: &str) -> Option<String> {
+        match self {
+            Self::Problem(id) => Some(format!(
+                "https://www.acmicpc.net/status?problem_id={}&user_id={}",
+                id, username
+            )),
+            Self::ContestProblem(_) => None,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct ExampleIO {
     pub(crate) input: String,
     pub(crate) output: String,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
 pub(crate) enum ProblemKind {
     SpecialJudge,       // spj
     Subtask,            // subtask
@@ -110,7 +126,7 @@ impl ProblemKind {
             }
         }
         if class.contains("problem-label-submit-limit") {
-            if let Some(count) = text.split(' ').last() {
+            if let Some(count) = text.split(' ').next_back() {
                 if let Ok(count) = count.parse::<usize>() {
                     return Ok(Self::SubmitLimit(count));
                 }
@@ -152,7 +168,7 @@ impl ProblemKind {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct Problem {
     pub(crate) id: ProblemId,
     pub(crate) title: String,
@@ -164,10 +180,319 @@ pub(crate) struct Problem {
     pub(crate) io: Vec<ExampleIO>,
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
+/// Checks if the submission status still shows waiting or judging.
+/// The text and the class can update separately, so both have to be decisive.
+pub(crate) fn is_judging(status_text: &str, status_class: &str) -> bool {
+    static CONTINUE_CLASS: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"result-wait|result-rejudge-wait|result-no-judge|result-compile|result-judging")
+            .unwrap()
+    });
+    static CONTINUE_TEXT: Lazy<Regex> = Lazy::new(|| Regex::new(r"채점|중|Pending|Judg").unwrap());
+    CONTINUE_CLASS.is_match(status_class) || CONTINUE_TEXT.is_match(status_text)
+}
+
+/// Status of a single submission row on the status page.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct SubmissionStatus {
+    pub(crate) text: String,
+    pub(crate) class: String,
+    /// Milliseconds
+    pub(crate) time: Option<u64>,
+    /// Kilobytes
+    pub(crate) memory: Option<u64>,
+}
+
+impl SubmissionStatus {
+    /// Builds the status from a response of the JSON status channel, taking the text and class
+    /// the status page would show from the response itself. Scores and judging progress are added to the text
+    /// the same way as on the status page.
+    /// Returns None if the response lacks them, so that the status page can be used instead.
+    pub(crate) fn from_status_json(data: &serde_json::Value) -> Option<Self> {
+        fn number(value: &serde_json::Value) -> Option<f64> {
+            match value {
+                serde_json::Value::Number(n) => n.as_f64(),
+                serde_json::Value::String(s) => s.trim().parse().ok(),
+                _ => None,
+            }
+        }
+        let mut text = data["result_name"].as_str()?.trim().to_string();
+        let class = data["result_class"].as_str()?.trim();
+        if text.is_empty() || class.is_empty() {
+            return None;
+        }
+        let class = if class.starts_with("result-") {
+            format!("result-text {}", class)
+        } else {
+            format!("result-text result-{}", class)
+        };
+        let score = number(&data["subtask_score"]).or_else(|| number(&data["partial_score"]));
+        if let Some(score) = score {
+            text = format!("{} ({}점)", text, score);
+        } else if let Some(progress) = number(&data["progress"]) {
+            if class.ends_with("result-judging") {
+                text = format!("{} ({}%)", text, progress);
+            }
+        }
+        Some(Self {
+            text,
+            class,
+            time: number(&data["time"]).map(|time| time as u64),
+            memory: number(&data["memory"]).map(|memory| memory as u64),
+        })
+    }
+}
+
+/// One row of the status page.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct StatusRow {
+    pub(crate) submission_id: String,
+    pub(crate) status: SubmissionStatus,
+    pub(crate) lang: String,
+    /// Bytes
+    pub(crate) length: Option<u64>,
+    /// Unix time in seconds
+    pub(crate) timestamp: Option<u64>,
+}
+
+/// Extracts the verdict (AC, WA, RE, ...) from the class of a submission status element.
+pub(crate) fn verdict_from_class(status_class: &str) -> Option<String> {
+    static RESULT: Lazy<Regex> = Lazy::new(|| Regex::new(r" result-([a-z]+)").unwrap());
+    let result = RESULT.captures(status_class)?[1].to_ascii_uppercase();
+    if result == "RTE" {
+        Some("RE".to_string())
+    } else {
+        Some(result)
+    }
+}
+
+/// One line of the submission log.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct SubmissionRecord {
+    /// Unix time in seconds
+    pub(crate) timestamp: u64,
+    pub(crate) problem: String,
+    pub(crate) title: String,
+    pub(crate) lang: String,
+    pub(crate) file: String,
+    pub(crate) source_hash: String,
+    pub(crate) source: String,
+    pub(crate) submission_id: Option<String>,
+    /// None if the judging was not watched until the end
+    pub(crate) verdict: Option<String>,
+    pub(crate) status: String,
+    /// Milliseconds
+    pub(crate) time: Option<u64>,
+    /// Kilobytes
+    pub(crate) memory: Option<u64>,
+    pub(crate) score: Option<f64>,
+}
+
+/// One line of the problem log, written on each `prob`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct ProblemRecord {
+    /// Unix time in seconds
+    pub(crate) timestamp: u64,
+    pub(crate) problem: String,
+    pub(crate) title: String,
+}
+
+/// Parses a `YYYY-MM-DD` date into the Unix time of its start in UTC.
+pub(crate) fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.split('-');
+    let year = parts.next()?.parse::<i64>().ok()?;
+    let month = parts.next()?.parse::<i64>().ok()?;
+    let day = parts.next()?.parse::<i64>().ok()?;
+    let leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+    // https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    u64::try_from(days * 86400).ok()
+}
+
+/// Summary of the problem and submission logs over a time range.
+pub(crate) struct Stats {
+    pub(crate) loaded: usize,
+    pub(crate) attempted: usize,
+    pub(crate) solved: usize,
+    /// Submissions until the first AC, averaged over solved problems
+    pub(crate) attempts_per_solve: Option<f64>,
+    /// (verdict, count), most frequent first
+    pub(crate) verdicts: Vec<(String, usize)>,
+    /// (problem, title, seconds from the last `prob` before the first submission to the first AC)
+    pub(crate) time_to_ac: Vec<(String, String, u64)>,
+    /// (language, submissions, AC count), most used first
+    pub(crate) languages: Vec<(String, usize, usize)>,
+}
+
+impl Stats {
+    /// Computes the stats of the records whose timestamps are in `from..to`.
+    pub(crate) fn new(
+        problems: &[ProblemRecord],
+        submissions: &[SubmissionRecord],
+        from: u64,
+        to: u64,
+    ) -> Self {
+        let in_range = |timestamp: u64| (from..to).contains(&timestamp);
+        let mut submissions = submissions
+            .iter()
+            .filter(|record| in_range(record.timestamp))
+            .collect::<Vec<_>>();
+        submissions.sort_by_key(|record| record.timestamp);
+        let is_ac = |record: &SubmissionRecord| record.verdict.as_deref() == Some("AC");
+
+        let mut loaded = problems
+            .iter()
+            .filter(|record| in_range(record.timestamp))
+            .map(|record| &record.problem)
+            .collect::<Vec<_>>();
+        loaded.sort();
+        loaded.dedup();
+
+        let mut attempted = submissions
+            .iter()
+            .map(|record| &record.problem)
+            .collect::<Vec<_>>();
+        attempted.sort();
+        attempted.dedup();
+
+        let mut solved = 0;
+        let mut attempts = 0;
+        let mut time_to_ac = vec![];
+        for &problem in &attempted {
+            let problem_submissions = submissions
+                .iter()
+                .filter(|record| &record.problem == problem)
+                .collect::<Vec<_>>();
+            let Some(first_ac) = problem_submissions.iter().position(|r| is_ac(r)) else {
+                continue;
+            };
+            solved += 1;
+            attempts += first_ac + 1;
+            let first_submission = problem_submissions[0].timestamp;
+            let ac_record = problem_submissions[first_ac];
+            let last_load = problems
+                .iter()
+                .filter(|record| &record.problem == problem && record.timestamp <= first_submission)
+                .map(|record| record.timestamp)
+                .max();
+            if let Some(last_load) = last_load {
+                time_to_ac.push((
+                    problem.clone(),
+                    ac_record.title.clone(),
+                    ac_record.timestamp - last_load,
+                ));
+            }
+        }
+
+        let mut verdicts = HashMap::<String, usize>::new();
+        let mut languages = HashMap::<String, (usize, usize)>::new();
+        for record in &submissions {
+            let verdict = record.verdict.clone().unwrap_or_else(|| "?".to_string());
+            *verdicts.entry(verdict).or_default() += 1;
+            let language = languages.entry(record.lang.clone()).or_default();
+            language.0 += 1;
+            if is_ac(record) {
+                language.1 += 1;
+            }
+        }
+        let mut verdicts = verdicts.into_iter().collect::<Vec<_>>();
+        verdicts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let mut languages = languages
+            .into_iter()
+            .map(|(lang, (count, ac))| (lang, count, ac))
+            .collect::<Vec<_>>();
+        languages.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        Self {
+            loaded: loaded.len(),
+            attempted: attempted.len(),
+            solved,
+            attempts_per_solve: (solved > 0).then(|| attempts as f64 / solved as f64),
+            verdicts,
+            time_to_ac,
+            languages,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Language {
+    pub(crate) id: String,
+    pub(crate) name: String,
+}
+
+/// Returns up to 3 language names similar to the query, most similar first.
+pub(crate) fn suggest_languages<'a>(languages: &'a [Language], query: &str) -> Vec<&'a str> {
+    fn normalize(s: &str) -> Vec<char> {
+        s.chars()
+            .filter(|c| !c.is_whitespace())
+            .flat_map(|c| c.to_lowercase())
+            .collect()
+    }
+    fn edit_distance(a: &[char], b: &[char]) -> usize {
+        let mut prev = (0..=b.len()).collect::<Vec<_>>();
+        for (i, ca) in a.iter().enumerate() {
+            let mut cur = vec![i + 1];
+            for (j, cb) in b.iter().enumerate() {
+                let cost = usize::from(ca != cb);
+                cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+            }
+            prev = cur;
+        }
+        prev[b.len()]
+    }
+    let query = normalize(query);
+    let threshold = (query.len() / 3).max(2);
+    let mut candidates = languages
+        .iter()
+        .filter_map(|lang| {
+            let name = normalize(&lang.name);
+            let distance = if name == query {
+                0
+            } else if name.windows(query.len().max(1)).any(|w| w == query) {
+                // a substring match is as good as a single typo
+                1
+            } else {
+                edit_distance(&query, &name)
+            };
+            (distance <= threshold).then_some((distance, &lang.name[..]))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates
+        .into_iter()
+        .take(3)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct Credentials {
     pub(crate) bojautologin: String,
     pub(crate) onlinejudge: String,
+    /// Account the cookies belong to, if known (e.g. from `login`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) username: Option<String>,
+}
+
+/// The cookies are never printed
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials").finish_non_exhaustive()
+    }
 }
 
 #[derive(Clone, serde::Deserialize)]
@@ -180,10 +505,212 @@ pub(crate) struct Preset {
     pub(crate) build: Option<String>,
     pub(crate) cmd: Option<String>,
     pub(crate) input: Option<String>,
+    pub(crate) watch: Option<String>,
+    pub(crate) deps: Option<String>,
+    pub(crate) autobuild: Option<bool>,
+    pub(crate) guard: Option<bool>,
+    pub(crate) open: Option<CodeOpen>,
+}
+
+/// Who can see the submitted code
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CodeOpen {
+    Public,
+    Private,
+    /// Public only if accepted
+    Ac,
+}
+
+impl CodeOpen {
+    /// Value of the `code_open` radio button on the submit page
+    pub(crate) fn form_value(&self) -> &'static str {
+        match self {
+            Self::Public => "open",
+            Self::Private => "close",
+            Self::Ac => "onlyaccepted",
+        }
+    }
+}
+
+/// Maps solution files to a submit language, either directly or through a preset's `lang`.
+#[derive(Clone, serde::Deserialize)]
+pub(crate) struct LangMapping {
+    /// File extension (`py` or `.py`) or glob (`*.py`, `src/bin/*.rs`)
+    pub(crate) pattern: String,
+    pub(crate) lang: Option<String>,
+    pub(crate) preset: Option<String>,
+}
+
+/// Settings for bundling local library code into the submitted source.
+#[derive(Clone, Default, serde::Deserialize)]
+pub(crate) struct BundleConfig {
+    /// Rust crates to inline, as crate name to the path of its `lib.rs`
+    #[serde(default)]
+    pub(crate) crates: HashMap<String, String>,
+    /// Directories to search for quoted `#include` files in C/C++
+    #[serde(default)]
+    pub(crate) include_dirs: Vec<String>,
+    /// Path to write the bundled source to; may contain `{}` for the problem number
+    pub(crate) output: Option<String>,
+}
+
+#[derive(Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Shrink {
+    Comments,
+    Whitespace,
+}
+
+/// Checks run on the source right before submission.
+#[derive(Clone, serde::Deserialize)]
+pub(crate) struct CheckConfig {
+    /// Regexes that must not appear in the source, e.g. `dbg!`
+    #[serde(default)]
+    pub(crate) deny: Vec<String>,
+    /// Code length limit in bytes
+    #[serde(default = "CheckConfig::default_max_length")]
+    pub(crate) max_length: usize,
+    /// Steps to apply in order while the source is over the code length limit
+    #[serde(default)]
+    pub(crate) shrink: Vec<Shrink>,
+}
+
+impl CheckConfig {
+    fn default_max_length() -> usize {
+        65536
+    }
+}
+
+/// Browser driven through WebDriver
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum BrowserKind {
+    #[default]
+    Firefox,
+    /// Chrome or Chromium, with chromedriver
+    #[serde(alias = "chromium")]
+    Chrome,
+}
+
+impl BrowserKind {
+    pub(crate) fn default_driver(&self) -> &'static str {
+        match self {
+            Self::Firefox => "geckodriver",
+            Self::Chrome => "chromedriver",
+        }
+    }
+}
+
+/// How the browser session is started.
+#[derive(Clone, Default, serde::Deserialize)]
+pub(crate) struct WebDriverConfig {
+    #[serde(default)]
+    pub(crate) browser: BrowserKind,
+    /// Driver binary; defaults to geckodriver or chromedriver depending on the browser
+    pub(crate) driver: Option<String>,
+    /// Port for the driver to listen on; a free port is picked if not given
+    pub(crate) port: Option<u16>,
+    /// URL of an already running WebDriver server; no driver is started if given
+    pub(crate) url: Option<String>,
+    /// Browser profile directory, which keeps the login between runs; a temporary profile is used if not given
+    pub(crate) profile: Option<String>,
+    /// File to keep the acmicpc.net cookies in between runs, so that the login and the WAF challenge are reused
+    /// without a persistent profile. `~/` is expanded to the home directory in both paths.
+    pub(crate) session_file: Option<String>,
+    /// Login page used by `login`; can point to a mock page for testing
+    pub(crate) login_page: Option<String>,
+    /// Extra capabilities, merged into the ones gaboja sets
+    #[serde(default)]
+    pub(crate) capabilities: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(serde::Deserialize)]
 pub(crate) struct BojConfig {
     pub(crate) start: Option<String>,
     pub(crate) preset: Vec<Preset>,
+    /// Short names for submit languages, e.g. `cpp = 'C++17'`
+    #[serde(default)]
+    pub(crate) lang_alias: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) lang_map: Vec<LangMapping>,
+    /// On `prob`, switch to the preset whose solution file exists for the problem
+    #[serde(default)]
+    pub(crate) auto_preset: bool,
+    pub(crate) bundle: Option<BundleConfig>,
+    pub(crate) check: Option<CheckConfig>,
+    /// Path of the submission log; defaults to `.gaboja/submissions.jsonl`
+    pub(crate) submission_log: Option<String>,
+    #[serde(default)]
+    pub(crate) webdriver: WebDriverConfig,
+    /// File with the login cookies; defaults to `~/.config/gaboja/credentials.toml`
+    pub(crate) credentials_file: Option<String>,
+    /// Command printing `<bojautologin> <onlinejudge>`, used instead of the credentials file
+    pub(crate) credentials_command: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_date_valid() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2024-02-29"), Some(1709164800));
+        assert_eq!(parse_date("2000-02-29"), Some(951782400));
+        assert_eq!(parse_date("2026-12-31"), Some(1798675200));
+    }
+
+    #[test]
+    fn parse_date_invalid_day() {
+        assert_eq!(parse_date("2024-02-30"), None);
+        assert_eq!(parse_date("2023-02-29"), None);
+        assert_eq!(parse_date("1900-02-29"), None);
+        assert_eq!(parse_date("2024-04-31"), None);
+        assert_eq!(parse_date("2024-01-00"), None);
+    }
+
+    #[test]
+    fn parse_date_invalid_format() {
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("2024-01"), None);
+        assert_eq!(parse_date("2024-01-01-01"), None);
+        assert_eq!(parse_date("2024/01/01"), None);
+        assert_eq!(parse_date("1969-12-31"), None);
+    }
+
+    #[test]
+    fn status_json_uses_payload_text_and_class() {
+        let data = serde_json::json!({
+            "result": 4, "result_name": "맞았습니다!!", "result_class": "ac", "time": 12, "memory": "2020",
+        });
+        let status = SubmissionStatus::from_status_json(&data).unwrap();
+        assert_eq!(status.text, "맞았습니다!!");
+        assert_eq!(status.class, "result-text result-ac");
+        assert_eq!(verdict_from_class(&status.class).as_deref(), Some("AC"));
+        assert_eq!((status.time, status.memory), (Some(12), Some(2020)));
+    }
+
+    #[test]
+    fn status_json_keeps_score_and_progress() {
+        let data = serde_json::json!({
+            "result_name": "맞았습니다!!", "result_class": "result-ac", "partial_score": 37.5,
+        });
+        let status = SubmissionStatus::from_status_json(&data).unwrap();
+        assert_eq!(status.text, "맞았습니다!! (37.5점)");
+        assert_eq!(status.class, "result-text result-ac");
+        let data = serde_json::json!({
+            "result_name": "채점 중", "result_class": "judging", "progress": 42,
+        });
+        let status = SubmissionStatus::from_status_json(&data).unwrap();
+        assert_eq!(status.text, "채점 중 (42%)");
+    }
+
+    #[test]
+    fn status_json_without_text_falls_back() {
+        let data = serde_json::json!({ "result": 4, "time": 12 });
+        assert!(SubmissionStatus::from_status_json(&data).is_none());
+        let data = serde_json::json!({ "result_name": "", "result_class": "ac" });
+        assert!(SubmissionStatus::from_status_json(&data).is_none());
+    }
 }
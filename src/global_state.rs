@@ -1,10 +1,26 @@
-use crate::data::{BojConfig, Credentials, Preset, Problem, ProblemId};
+use crate::data::{
+    BojConfig, BundleConfig, CheckConfig, CodeOpen, Credentials, LangMapping, Language, Preset,
+    Problem, ProblemId, WebDriverConfig,
+};
 use crate::infra::browser::Browser;
+use crate::infra::console::{report_notice, Spinner};
+use crate::infra::credentials::{warn_tracked_credentials, CredentialStore};
+use crate::infra::daemon::DAEMON_SOCKET;
+use crate::infra::tracker::Tracker;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use once_cell::unsync::OnceCell;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver};
+use std::time::SystemTime;
+
+/// Where the source hashes of the last successful builds are kept
+pub(crate) const BUILD_HASHES_FILE: &str = ".gaboja/build_hashes.json";
 
 pub(crate) struct GlobalState {
-    pub(crate) credentials: Credentials,
+    /// Set by `set credentials` or `login`; otherwise read from the credential store when the browser needs to log in
+    pub(crate) credentials: RefCell<Credentials>,
     pub(crate) problem: Option<Problem>,
     pub(crate) init: String,
     pub(crate) build: String,
@@ -12,23 +28,58 @@ pub(crate) struct GlobalState {
     pub(crate) input: String,
     pub(crate) lang: String,
     pub(crate) file: String,
-    pub(crate) browser: Browser,
+    pub(crate) watch: String,
+    pub(crate) deps: String,
+    pub(crate) autobuild: bool,
+    pub(crate) guard: bool,
+    /// Code visibility on submit; the submit page default if None
+    pub(crate) open: Option<CodeOpen>,
+    /// Started on first use; see `browser()`
+    pub(crate) browser: OnceCell<Browser>,
+    /// Watches submissions in the background, sharing the browser session
+    pub(crate) tracker: OnceCell<Tracker>,
+    /// Running commands from the command line instead of the REPL
+    pub(crate) batch: bool,
+    /// Set when the last command ran tests or judging that did not pass; decides the exit code in batch mode
+    pub(crate) failed: bool,
     pub(crate) problem_cache: HashMap<ProblemId, Problem>,
     pub(crate) presets: HashMap<String, Preset>,
+    /// Preset names in the order of boj.toml
+    pub(crate) preset_order: Vec<String>,
+    pub(crate) lang_map: Vec<LangMapping>,
+    pub(crate) auto_preset: bool,
+    /// Bundling is done only if boj.toml has a `[bundle]` section
+    pub(crate) bundle: Option<BundleConfig>,
+    pub(crate) check: Option<CheckConfig>,
+    pub(crate) submission_log: PathBuf,
+    pub(crate) webdriver: WebDriverConfig,
+    /// Credentials file or command configured in boj.toml
+    pub(crate) credential_store: CredentialStore,
+    /// Unix time in seconds when this session started
+    pub(crate) session_start: u64,
+    /// Submit languages scraped from the submit page, fetched on first use
+    pub(crate) languages: Option<Vec<Language>>,
+    /// Set when the language list could not be fetched, so that it is not tried again in this session
+    pub(crate) languages_unavailable: bool,
+    pub(crate) lang_aliases: HashMap<String, String>,
+    /// Source hash at the time of the last successful build, keyed by the build command.
+    /// Kept in BUILD_HASHES_FILE between runs.
+    pub(crate) build_hashes: HashMap<String, u64>,
     pub(crate) ctrlc_channel: Receiver<()>,
 }
 
 impl GlobalState {
-    pub(crate) fn new() -> anyhow::Result<Self> {
+    pub(crate) fn new(batch: bool) -> anyhow::Result<Self> {
         let (sender, receiver) = channel();
         ctrlc::set_handler(move || {
             sender.send(()).unwrap();
         })?;
         let mut state = Self {
-            credentials: Credentials {
+            credentials: RefCell::new(Credentials {
                 bojautologin: String::new(),
                 onlinejudge: String::new(),
-            },
+                username: None,
+            }),
             problem: None,
             init: String::new(),
             build: "cargo build --release".to_string(),
@@ -36,16 +87,52 @@ impl GlobalState {
             input: "input.txt".to_string(),
             lang: "Rust 2021".to_string(),
             file: "src/main.rs".to_string(),
-            browser: Browser::new()?,
+            watch: String::new(),
+            deps: String::new(),
+            autobuild: true,
+            guard: false,
+            open: None,
+            browser: OnceCell::new(),
+            tracker: OnceCell::new(),
+            batch,
+            failed: false,
             problem_cache: HashMap::new(),
             presets: HashMap::new(),
+            preset_order: vec![],
+            lang_map: vec![],
+            auto_preset: false,
+            bundle: None,
+            check: None,
+            submission_log: PathBuf::from(".gaboja/submissions.jsonl"),
+            webdriver: WebDriverConfig::default(),
+            credential_store: CredentialStore::new(None),
+            session_start: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)?
+                .as_secs(),
+            languages: None,
+            languages_unavailable: false,
+            lang_aliases: HashMap::new(),
+            build_hashes: load_build_hashes(),
             ctrlc_channel: receiver,
         };
         // println!("state initialized");
-        match BojConfig::from_config() {
+        let config = BojConfig::from_config();
+        state.credential_store = CredentialStore::new(config.as_ref().ok());
+        match config {
             Ok(config) => {
+                warn_tracked_credentials(&config);
+                state.lang_aliases = config.lang_alias;
+                state.lang_map = config.lang_map;
+                state.auto_preset = config.auto_preset;
+                state.bundle = config.bundle;
+                state.check = config.check;
+                state.webdriver = config.webdriver;
+                if let Some(submission_log) = config.submission_log {
+                    state.submission_log = PathBuf::from(submission_log);
+                }
                 for preset in &config.preset {
                     state.presets.insert(preset.name.clone(), preset.clone());
+                    state.preset_order.push(preset.name.clone());
                 }
                 if let Some(start) = config.start.as_ref() {
                     for (lineno, line) in start.lines().enumerate() {
@@ -55,20 +142,20 @@ impl GlobalState {
                         match line.parse::<crate::InputCommand>() {
                             Ok(cmd) => {
                                 if let Err(err) = state.execute(&cmd) {
-                                    println!(
+                                    report_notice(&format!(
                                         "boj.toml start script execution error at line {}: {}",
                                         lineno + 1,
                                         err
-                                    );
+                                    ));
                                     break;
                                 }
                             }
                             Err(err) => {
-                                println!(
+                                report_notice(&format!(
                                     "boj.toml start script parse error at line {}: {}",
                                     lineno + 1,
                                     err
-                                );
+                                ));
                                 break;
                             }
                         }
@@ -80,19 +167,139 @@ impl GlobalState {
         Ok(state)
     }
 
+    /// The problem log is kept next to the submission log.
+    pub(crate) fn problem_log(&self) -> PathBuf {
+        self.submission_log.with_file_name("problems.jsonl")
+    }
+
+    pub(crate) fn save_build_hashes(&self) -> anyhow::Result<()> {
+        let path = Path::new(BUILD_HASHES_FILE);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string(&self.build_hashes)?)?;
+        Ok(())
+    }
+
+    /// Returns the browser, starting it and logging in with the stored credentials on first use.
+    pub(crate) fn browser(&self) -> anyhow::Result<&Browser> {
+        self.browser.get_or_try_init(|| {
+            let browser = match Browser::connect(Path::new(DAEMON_SOCKET)) {
+                Some(browser) => {
+                    report_notice(&format!("Attached to the daemon on {}", DAEMON_SOCKET));
+                    browser
+                }
+                None => Browser::new(&self.webdriver)?,
+            };
+            // a saved session, or the daemon's one, may still be logged in
+            if let Some(username) = browser.get_username()? {
+                let session = if browser.is_remote() {
+                    "daemon"
+                } else {
+                    "saved"
+                };
+                report_notice(&format!("Logged in as {} ({} session)", username, session));
+                if !self.credentials.borrow().bojautologin.is_empty()
+                    && self.keep_credentials(&browser, &username)
+                {
+                    browser.set_credentials(&self.credentials.borrow())?;
+                }
+                return Ok(browser);
+            }
+            // `set credentials` in the start script takes precedence over the store.
+            // The store is read only here, as credentials_command may ask for a passphrase.
+            if self.credentials.borrow().bojautologin.is_empty() {
+                match self.credential_store.load() {
+                    Ok(Some(credentials)) => *self.credentials.borrow_mut() = credentials,
+                    Ok(None) => {}
+                    Err(err) => report_notice(&format!("Failed to load the credentials: {}", err)),
+                }
+            }
+            if !self.credentials.borrow().bojautologin.is_empty() {
+                self.login(&browser)?;
+            } else if !self.batch {
+                report_notice("Not logged in; use `login` or `set credentials` to log in");
+            }
+            Ok(browser)
+        })
+    }
+
+    /// Decides whether a browser already logged in as `username` may keep the credentials
+    /// to log in again when its session expires. Credentials of another account are not kept,
+    /// and as the daemon is shared, it gets credentials of an unknown account only if the user agrees.
+    fn keep_credentials(&self, browser: &Browser, username: &str) -> bool {
+        match self.credentials.borrow().username.as_deref() {
+            Some(owner) if owner == username => true,
+            Some(owner) => {
+                report_notice(&format!(
+                    "Warning: The session is logged in as {}, but the credentials are for {}; keeping the session (use `login` to switch)",
+                    username, owner
+                ));
+                false
+            }
+            None if !browser.is_remote() => true,
+            None if self.batch => false,
+            None => Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!(
+                    "The daemon is logged in as {}. Let it log in again with your credentials when the session expires?",
+                    username
+                ))
+                .default(false)
+                .interact()
+                .unwrap_or(false),
+        }
+    }
+
+    pub(crate) fn tracker(&self) -> anyhow::Result<&Tracker> {
+        self.tracker
+            .get_or_try_init(|| Ok(Tracker::new(self.browser()?.clone())))
+    }
+
+    pub(crate) fn login(&self, browser: &Browser) -> anyhow::Result<()> {
+        let spinner = Spinner::new("Logging in...");
+        browser.login(&self.credentials.borrow())?;
+        if let Some(username) = browser.get_username()? {
+            spinner.finish(&format!("Logged in as {}", username));
+        } else {
+            spinner.abandon("Login failed with the credentials provided");
+        }
+        Ok(())
+    }
+
     pub(crate) fn quit(self) -> anyhow::Result<()> {
-        self.browser.quit()
+        if let Some(tracker) = self.tracker.get() {
+            tracker.print_reports();
+            let pending = tracker.log_pending();
+            if pending > 0 {
+                report_notice(&format!(
+                    "{} submission(s) still judging were logged as pending",
+                    pending
+                ));
+            }
+        }
+        match self.browser.into_inner() {
+            Some(browser) => browser.quit(),
+            None => Ok(()),
+        }
     }
 }
 
+/// A missing or broken file only means that the next build is not skipped.
+fn load_build_hashes() -> HashMap<String, u64> {
+    std::fs::read_to_string(BUILD_HASHES_FILE)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
 impl BojConfig {
-    fn from_config() -> anyhow::Result<Self> {
+    pub(crate) fn from_config() -> anyhow::Result<Self> {
         let mut boj_toml = std::env::current_dir()?;
         boj_toml.push("boj.toml");
         let boj_toml_content = std::fs::read_to_string(boj_toml)?;
         let config = toml::from_str(&boj_toml_content);
         if let Err(error) = &config {
-            println!("boj.toml parse error:\n{}", error);
+            report_notice(&format!("boj.toml parse error:\n{}", error));
         }
         Ok(config?)
     }
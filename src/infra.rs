@@ -1,3 +1,10 @@
 pub(crate) mod browser;
+pub(crate) mod bundler;
 pub(crate) mod console;
+pub(crate) mod credentials;
+pub(crate) mod daemon;
+pub(crate) mod files;
+pub(crate) mod shrink;
+pub(crate) mod submission_log;
 pub(crate) mod subprocess;
+pub(crate) mod tracker;
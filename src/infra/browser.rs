@@ -1,22 +1,60 @@
-use crate::data::{ExampleIO, Problem, ProblemId, ProblemKind};
-use crate::infra::console::Spinner;
-use crate::infra::subprocess::{spawn_cmd_background, run_silent};
+use crate::data::{
+    BrowserKind, CodeOpen, Credentials, ExampleIO, Language, Problem, ProblemId, ProblemKind,
+    StatusRow, SubmissionStatus, WebDriverConfig,
+};
+use crate::infra::console::{report_notice, Spinner};
+use crate::infra::daemon::DaemonClient;
+use crate::infra::files::{expand_home, write_private};
+use crate::infra::subprocess::{spawn_owned, OwnedChild};
 use std::future::Future;
-use std::path::Path;
-use std::process::Stdio;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use thirtyfour::common::cookie::SameSite;
 use thirtyfour::prelude::*;
 use tokio::runtime;
 
-/// Takes care of interaction with BOJ pages. Internally uses headless Firefox and geckodriver.
+/// Takes care of interaction with BOJ pages. Internally uses headless Firefox and geckodriver,
+/// either owned by this process or by a `gaboja daemon` that the calls are forwarded to.
+/// Clones share the same browser session.
+#[derive(Clone)]
 pub(crate) struct Browser {
-    webdriver: WebDriver,
+    backend: Backend,
+    credentials: Arc<Mutex<Option<Credentials>>>,
+}
+
+#[derive(Clone)]
+enum Backend {
+    /// `driver` is the WebDriver server started for the session, if any.
+    /// Cookies are kept in `session_file` between runs if it is given.
+    Local {
+        webdriver: WebDriver,
+        driver: Option<Arc<OwnedChild>>,
+        session_file: Option<PathBuf>,
+    },
+    Remote(DaemonClient),
+}
+
+/// Returns the local WebDriver, or forwards the call to the daemon and returns its result.
+macro_rules! driver_or_forward {
+    ($self:ident, $method:literal $(, $arg:expr)*) => {
+        match &$self.backend {
+            Backend::Local { webdriver, .. } => webdriver,
+            Backend::Remote(daemon) => {
+                return daemon.call($method, serde_json::json!([$($arg),*]));
+            }
+        }
+    };
 }
 
 fn with_async_runtime<F, R>(future: F) -> anyhow::Result<R>
 where
     F: Future<Output = anyhow::Result<R>>,
 {
+    // Background submission tracking shares the session, so commands must not interleave
+    static BROWSER_LOCK: Mutex<()> = Mutex::new(());
+    let _lock = BROWSER_LOCK.lock().unwrap_or_else(|err| err.into_inner());
     let rt = runtime::Builder::new_current_thread()
         .enable_time()
         .enable_io()
@@ -24,27 +62,346 @@ where
     rt.block_on(future)
 }
 
+/// Asks the OS for a port that is free right now.
+fn free_port() -> anyhow::Result<u16> {
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    Ok(listener.local_addr()?.port())
+}
+
+/// Waits up to 10 seconds for the driver to accept connections on the port.
+async fn wait_for_port(port: u16) -> bool {
+    for _ in 0..100 {
+        if tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .is_ok()
+        {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+/// Builds the capabilities for the configured browser.
+/// The browser runs headless to allow running without a graphic device.
+fn capabilities(config: &WebDriverConfig) -> anyhow::Result<Capabilities> {
+    let mut caps: Capabilities = match config.browser {
+        BrowserKind::Firefox => {
+            let mut caps = DesiredCapabilities::firefox();
+            caps.set_headless()?;
+            if let Some(profile) = &config.profile {
+                let profile = expand_home(profile);
+                std::fs::create_dir_all(&profile)?;
+                caps.add_arg("-profile")?;
+                caps.add_arg(&profile.to_string_lossy())?;
+            }
+            caps.into()
+        }
+        BrowserKind::Chrome => {
+            let mut caps = DesiredCapabilities::chrome();
+            caps.set_headless()?;
+            if let Some(profile) = &config.profile {
+                let profile = expand_home(profile);
+                caps.add_arg(&format!("--user-data-dir={}", profile.display()))?;
+            }
+            caps.into()
+        }
+    };
+    for (key, value) in &config.capabilities {
+        match caps.get_mut(key) {
+            Some(current) => merge_json(current, value.clone()),
+            None => {
+                caps.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    Ok(caps)
+}
+
+/// Merges `extra` into `base`: objects are merged key by key, and anything else is replaced.
+fn merge_json(base: &mut serde_json::Value, extra: serde_json::Value) {
+    match (base, extra) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(extra)) => {
+            for (key, value) in extra {
+                match base.get_mut(&key) {
+                    Some(current) => merge_json(current, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, extra) => *base = extra,
+    }
+}
+
+/// Default page for `login_with_password`
+pub(crate) const LOGIN_PAGE: &str = "https://www.acmicpc.net/login";
+
+/// Names of the login form fields filled by `login_with_password`
+const LOGIN_USERNAME_FIELD: &str = "login_user_id";
+const LOGIN_PASSWORD_FIELD: &str = "login_password";
+const LOGIN_REMEMBER_FIELD: &str = "auto_login";
+/// Id of the login form's submit button
+const LOGIN_SUBMIT_BUTTON: &str = "submit_button";
+
+/// Sets the saved acmicpc.net cookies, including the login and AWS WAF ones.
+/// Restores the cookies saved by `save_session`. Cookies that cannot be read or set,
+/// e.g. expired ones, are skipped; returns how many were.
+async fn load_session(driver: &WebDriver, path: &Path) -> anyhow::Result<usize> {
+    let cookies: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    // Cookies can only be set on a page of their domain; robots.txt is light and not behind the challenge
+    driver.get("https://www.acmicpc.net/robots.txt").await?;
+    let mut skipped = 0;
+    for cookie in cookies {
+        let Ok(cookie) = serde_json::from_value::<Cookie>(cookie) else {
+            skipped += 1;
+            continue;
+        };
+        if is_boj_cookie(&cookie) && driver.add_cookie(cookie).await.is_err() {
+            skipped += 1;
+        }
+    }
+    Ok(skipped)
+}
+
+/// Saves the acmicpc.net cookies, leaving out those of other sites the browser visited.
+async fn save_session(driver: &WebDriver, path: &Path) -> anyhow::Result<()> {
+    let cookies = driver.get_all_cookies().await?;
+    let cookies = cookies
+        .into_iter()
+        .filter(is_boj_cookie)
+        .collect::<Vec<_>>();
+    write_private(path, &serde_json::to_string_pretty(&cookies)?)?;
+    Ok(())
+}
+
+fn is_boj_cookie(cookie: &Cookie) -> bool {
+    cookie.domain.as_deref().is_some_and(|domain| {
+        let domain = domain.trim_start_matches('.');
+        domain == "acmicpc.net" || domain.ends_with(".acmicpc.net")
+    })
+}
+
+/// Checks whether the current page shows the user as logged out,
+/// either by the redirect to the login page or by the missing username in the header.
+async fn logged_out(driver: &WebDriver) -> anyhow::Result<bool> {
+    if driver.current_url().await?.path().starts_with("/login") {
+        return Ok(true);
+    }
+    let username_elem = driver.query(By::ClassName("username")).first_opt().await?;
+    Ok(username_elem.is_none())
+}
+
+/// Makes credentials from the cookies present after submitting the login form.
+/// The remember-me cookie is set only if the login succeeded;
+/// otherwise the login page is shown again with an error, so None means the login was rejected.
+fn login_credentials(
+    bojautologin: Option<Cookie>,
+    onlinejudge: Option<Cookie>,
+    username: &str,
+) -> anyhow::Result<Option<Credentials>> {
+    let Some(bojautologin) = bojautologin else {
+        return Ok(None);
+    };
+    let Some(onlinejudge) = onlinejudge else {
+        anyhow::bail!("Logged in, but the OnlineJudge session cookie is missing");
+    };
+    Ok(Some(Credentials {
+        bojautologin: bojautologin.value,
+        onlinejudge: onlinejudge.value,
+        username: Some(username.to_string()),
+    }))
+}
+
+/// Sets the login cookies and reloads the main page.
+async fn set_login_cookies(driver: &WebDriver, credentials: &Credentials) -> anyhow::Result<()> {
+    // Browser is already on acmicpc.net; safe to set cookies
+    for (name, value) in [
+        ("bojautologin", &credentials.bojautologin),
+        ("OnlineJudge", &credentials.onlinejudge),
+    ] {
+        let mut cookie = Cookie::new(name, value.clone());
+        cookie.set_domain(".acmicpc.net");
+        cookie.set_path("/");
+        cookie.set_same_site(SameSite::Lax);
+        driver.add_cookie(cookie).await?;
+    }
+    driver.get("https://www.acmicpc.net").await?;
+    Ok(())
+}
+
+/// Opens the submit page. Returns false if it redirected to the login page, i.e. the session expired.
+async fn open_submit_page(driver: &WebDriver, problem_id: &ProblemId) -> anyhow::Result<bool> {
+    driver.get(problem_id.submit_url()).await?;
+    if driver.current_url().await?.path().starts_with("/login") {
+        return Ok(false);
+    }
+    if logged_out(driver).await? {
+        anyhow::bail!("Not logged in");
+    }
+    Ok(true)
+}
+
+/// Fills in and sends the form on the submit page, and returns the submission id read from the status page it redirects to.
+async fn submit_form(
+    driver: &WebDriver,
+    source: &str,
+    language: &str,
+    language_id: Option<&str>,
+    code_open: Option<CodeOpen>,
+) -> anyhow::Result<Option<String>> {
+    if let Some(language_id) = language_id {
+        // Set language: set the value of the underlying select and let chosen refresh the dropdown
+        driver
+            .execute(
+                "const select = document.querySelector('#language'); select.value = arguments[0]; select.dispatchEvent(new Event('change')); if (window.jQuery) { jQuery(select).trigger('chosen:updated'); }",
+                vec![serde_json::to_value(language_id)?],
+            )
+            .await?;
+    } else {
+        // Set language: click dropdown, search name, select first item
+        let lang_elem = driver.query(By::ClassName("chosen-single")).first().await?;
+        lang_elem.click().await?;
+        let lang_search_elem = driver
+            .query(By::ClassName("chosen-search-input"))
+            .first()
+            .await?;
+        lang_search_elem.send_keys(language).await?;
+        let lang_found_elem = driver
+            .query(By::Css(".active-result.highlighted"))
+            .first()
+            .await?;
+        lang_found_elem.click().await?;
+    }
+    // The first search result may not be the exact language, so check the selection
+    let selected = driver
+        .execute(
+            "return document.querySelector('#language').selectedOptions[0].textContent.trim()",
+            vec![],
+        )
+        .await?
+        .convert::<String>()?;
+    if selected != language {
+        anyhow::bail!(
+            "Selected language `{}` does not match `{}`",
+            selected,
+            language
+        );
+    }
+
+    // Set source: https://stackoverflow.com/a/57621139/4595904 simplified
+    // `send_keys` is incorrect, as bracket/quote matching will be triggered as the source code is typed,
+    // resulting in CE (https://www.acmicpc.net/source/78678130)
+    // Clipboard API seems to require user permission, so inject the string to CodeMirror instance
+    driver
+        .execute(
+            "document.querySelector('.CodeMirror').CodeMirror.setValue(arguments[0])",
+            vec![serde_json::to_value(source)?],
+        )
+        .await?;
+
+    // Set code visibility; the page default is kept if not given
+    if let Some(code_open) = code_open {
+        let found = driver
+            .execute(
+                "const radio = document.querySelector(`input[name='code_open'][value='${arguments[0]}']`); if (radio) { radio.checked = true; } return !!radio;",
+                vec![serde_json::to_value(code_open.form_value())?],
+            )
+            .await?
+            .convert::<bool>()?;
+        if !found {
+            anyhow::bail!("Code visibility option not found on the submit page");
+        }
+    }
+
+    // Submit and wait until refresh starts
+    let submit_elem = driver.query(By::Id("submit_button")).first().await?;
+    submit_elem.click().await?;
+    // The code is already submitted here, so nothing after this may fail the submission.
+    // If the wait fails, reading the id below most likely fails too and the id stays unknown.
+    submit_elem.wait_until().stale().await.ok();
+
+    // Doing this in the same call keeps another client of the daemon from submitting in between.
+    let id: Result<_, WebDriverError> = async {
+        let row = driver
+            .query(By::Css("#status-table tbody tr"))
+            .first()
+            .await?;
+        match row.query(By::Css("td")).first_opt().await? {
+            Some(elem) => Ok(Some(elem.text().await?.trim().to_string())),
+            None => Ok(None),
+        }
+    }
+    .await;
+    Ok(id.unwrap_or(None))
+}
+
+fn leading_number(text: &str) -> Option<u64> {
+    let digits = text
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>();
+    digits.parse().ok()
+}
+
+fn is_html_page(text: &str) -> bool {
+    let start = text.trim_start().chars().take(14).collect::<String>();
+    let start = start.to_ascii_lowercase();
+    start.starts_with("<!doctype html") || start.starts_with("<html")
+}
+
 impl Browser {
     /// Creates a new browser context. This method handles AWS WAF challenge.
-    pub(crate) fn new() -> anyhow::Result<Self> {
+    pub(crate) fn new(config: &WebDriverConfig) -> anyhow::Result<Self> {
         with_async_runtime(async {
-            let spinner = Spinner::new("Starting geckodriver...");
-            spawn_cmd_background("geckodriver")
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .spawn()?;
-            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-
-            spinner.set_message("Starting Firefox...");
-            // Use headless firefox to allow running without a graphic device
-            let mut caps = DesiredCapabilities::firefox();
-            caps.set_headless()?;
-            // println!("webdriver initializing");
-            let webdriver = WebDriver::new("http://localhost:4444", caps).await?;
-            // println!("webdriver initialized");
+            let driver = config
+                .driver
+                .clone()
+                .unwrap_or_else(|| config.browser.default_driver().to_string());
+            let (url, driver) = match &config.url {
+                Some(url) => (url.clone(), None),
+                None => {
+                    let spinner = Spinner::new(&format!("Starting {}...", driver));
+                    let port = match config.port {
+                        Some(port) => port,
+                        None => free_port()?,
+                    };
+                    let child = spawn_owned(&driver, &[format!("--port={}", port)])?;
+                    if !wait_for_port(port).await {
+                        spinner.abandon(&format!("{} did not start", driver));
+                        anyhow::bail!(
+                            "No WebDriver is listening on port {}; check that `{}` is installed",
+                            port,
+                            driver
+                        );
+                    }
+                    spinner.finish(&format!("{} listening on port {}", driver, port));
+                    (format!("http://localhost:{}", port), Some(child))
+                }
+            };
+
+            let spinner = Spinner::new("Starting the browser...");
+            let webdriver = WebDriver::new(&url, capabilities(config)?).await?;
+
+            let session_file = config.session_file.as_deref().map(expand_home);
+            if let Some(path) = session_file.as_deref().filter(|path| path.exists()) {
+                spinner.set_message("Restoring the saved session...");
+                match load_session(&webdriver, path).await {
+                    Ok(0) => {}
+                    Ok(skipped) => report_notice(&format!(
+                        "Skipped {} saved cookies that could not be restored",
+                        skipped
+                    )),
+                    Err(err) => {
+                        report_notice(&format!("Failed to restore the saved session: {}", err))
+                    }
+                }
+            }
 
             spinner.set_message("Waiting for redirect to acmicpc.net...");
-            // Handle AWS WAF challenge
+            // Handle AWS WAF challenge; a restored WAF cookie usually skips it
             webdriver.get("https://www.acmicpc.net").await?;
 
             // If this container exists, AWS challenge is activated; wait until refresh starts
@@ -58,35 +415,130 @@ impl Browser {
 
             spinner.finish("Browser initialization complete");
             Ok(Self {
-                webdriver,
+                backend: Backend::Local {
+                    webdriver,
+                    driver,
+                    session_file,
+                },
+                credentials: Arc::new(Mutex::new(None)),
             })
         })
     }
 
-    /// Sets BOJ credential cookies.
-    pub(crate) fn login(&self, bojautologin: &str, onlinejudge: &str) -> anyhow::Result<()> {
+    /// Attaches to the daemon listening on the socket, if there is one.
+    pub(crate) fn connect(socket: &Path) -> Option<Self> {
+        let daemon = DaemonClient::new(socket);
+        daemon.call::<()>("ping", serde_json::json!([])).ok()?;
+        Some(Self {
+            backend: Backend::Remote(daemon),
+            credentials: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn session_file(&self) -> Option<&Path> {
+        match &self.backend {
+            Backend::Local { session_file, .. } => session_file.as_deref(),
+            Backend::Remote(_) => None,
+        }
+    }
+
+    /// True if the browser is owned by a daemon.
+    pub(crate) fn is_remote(&self) -> bool {
+        matches!(self.backend, Backend::Remote(_))
+    }
+
+    /// Sets BOJ credential cookies. The credentials are kept to log in again when the session expires.
+    pub(crate) fn login(&self, credentials: &Credentials) -> anyhow::Result<()> {
+        let driver = driver_or_forward!(self, "login", credentials);
+        self.set_credentials(credentials)?;
         with_async_runtime(async {
-            let driver = &self.webdriver;
-
-            // Browser is already on acmicpc.net; safe to set cookies
-            let mut cookie = Cookie::new("bojautologin", bojautologin);
-            cookie.set_domain(".acmicpc.net");
-            cookie.set_path("/");
-            cookie.set_same_site(SameSite::Lax);
-            driver.add_cookie(cookie.clone()).await?;
-            let mut cookie = Cookie::new("OnlineJudge", onlinejudge);
-            cookie.set_domain(".acmicpc.net");
-            cookie.set_path("/");
-            cookie.set_same_site(SameSite::Lax);
-            driver.add_cookie(cookie.clone()).await?;
-            driver.get("https://www.acmicpc.net").await?;
+            set_login_cookies(driver, credentials).await?;
+            if let Some(path) = self.session_file() {
+                save_session(driver, path).await?;
+            }
             Ok(())
         })
     }
 
+    /// Logs in through the login form with "remember me" checked, and returns the resulting login cookies.
+    /// Returns None if the login was rejected. The credentials are kept as with `login`.
+    pub(crate) fn login_with_password(
+        &self,
+        login_page: &str,
+        username: &str,
+        password: &str,
+    ) -> anyhow::Result<Option<Credentials>> {
+        let driver =
+            driver_or_forward!(self, "login_with_password", login_page, username, password);
+        let credentials = with_async_runtime(async {
+            // Start logged out, so that the old cookies are not mistaken for the new ones
+            driver.get(login_page).await?;
+            driver.delete_cookie("bojautologin").await?;
+            driver.delete_cookie("OnlineJudge").await?;
+            driver.refresh().await?;
+            let form = driver.query(By::Css("form")).first().await?;
+            form.find(By::Name(LOGIN_USERNAME_FIELD))
+                .await?
+                .send_keys(username)
+                .await?;
+            form.find(By::Name(LOGIN_PASSWORD_FIELD))
+                .await?
+                .send_keys(password)
+                .await?;
+            let remember = form.find(By::Name(LOGIN_REMEMBER_FIELD)).await?;
+            if !remember.is_selected().await? {
+                remember.click().await?;
+            }
+            let submit_elem = form.find(By::Id(LOGIN_SUBMIT_BUTTON)).await?;
+            submit_elem.click().await?;
+            submit_elem.wait_until().stale().await?;
+
+            let credentials = login_credentials(
+                driver.get_named_cookie("bojautologin").await.ok(),
+                driver.get_named_cookie("OnlineJudge").await.ok(),
+                username,
+            )?;
+            if credentials.is_some() {
+                if let Some(path) = self.session_file() {
+                    save_session(driver, path).await?;
+                }
+            }
+            Ok(credentials)
+        })?;
+        if let Some(credentials) = &credentials {
+            self.set_credentials(credentials)?;
+        }
+        Ok(credentials)
+    }
+
+    /// Keeps the credentials to log in again when the session expires, without logging in now.
+    pub(crate) fn set_credentials(&self, credentials: &Credentials) -> anyhow::Result<()> {
+        driver_or_forward!(self, "set_credentials", credentials);
+        *self.credentials.lock().unwrap() = Some(credentials.clone());
+        Ok(())
+    }
+
+    /// Logs in again after the session expired, with the credentials kept by `login` or `set_credentials`.
+    async fn relogin(&self, driver: &WebDriver) -> anyhow::Result<()> {
+        let Some(credentials) = self.credentials.lock().unwrap().clone() else {
+            anyhow::bail!("Not logged in; use `login` or `set credentials` to log in");
+        };
+        report_notice("Session expired; logging in again");
+        set_login_cookies(driver, &credentials).await?;
+        if logged_out(driver).await? {
+            anyhow::bail!(
+                "The stored credentials are no longer valid; get new cookies and run `set credentials` again"
+            );
+        }
+        if let Some(path) = self.session_file() {
+            save_session(driver, path).await?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn get_username(&self) -> anyhow::Result<Option<String>> {
+        let driver = driver_or_forward!(self, "get_username");
         with_async_runtime(async {
-            let driver = &self.webdriver;
             // Browser is already on acmicpc.net
             let username_elem = driver.query(By::ClassName("username")).first_opt().await?;
             let username = if let Some(elem) = username_elem {
@@ -100,8 +552,8 @@ impl Browser {
 
     /// Fetches relevant information of the given problem.
     pub(crate) fn get_problem(&self, problem_id: &ProblemId) -> anyhow::Result<Problem> {
+        let driver = driver_or_forward!(self, "get_problem", problem_id);
         with_async_runtime(async {
-            let driver = &self.webdriver;
             let problem_page = problem_id.problem_url();
             driver.get(problem_page).await?;
             let fc_cta_consent = driver.find_all(By::ClassName("fc-cta-consent")).await?;
@@ -165,88 +617,361 @@ impl Browser {
         })
     }
 
-    /// Submits source code via submit page.
+    /// Submits source code via submit page and returns the submission id read from the status page it redirects to.
+    /// If the language id is known, the language is selected by id; otherwise it is searched by name.
+    /// If the session has expired, logs in again with the stored credentials and tries once more.
     pub(crate) fn submit_solution(
         &self,
         problem_id: &ProblemId,
         source: &str,
         language: &str,
-    ) -> anyhow::Result<()> {
+        language_id: Option<&str>,
+        code_open: Option<CodeOpen>,
+    ) -> anyhow::Result<Option<String>> {
+        let driver = driver_or_forward!(
+            self,
+            "submit_solution",
+            problem_id,
+            source,
+            language,
+            language_id,
+            code_open
+        );
         with_async_runtime(async {
-            let driver = &self.webdriver;
-            let submit_page = problem_id.submit_url();
-            driver.get(submit_page).await?;
+            // Logging in again is only tried before the form is filled in, so the code is never sent twice
+            if !open_submit_page(driver, problem_id).await? {
+                self.relogin(driver).await?;
+                if !open_submit_page(driver, problem_id).await? {
+                    anyhow::bail!("Not logged in");
+                }
+            }
+            submit_form(driver, source, language, language_id, code_open).await
+        })
+    }
 
-            // Set language: click dropdown, search name, select first item
-            let lang_elem = driver.query(By::ClassName("chosen-single")).first().await?;
-            lang_elem.click().await?;
-            let lang_search_elem = driver
-                .query(By::ClassName("chosen-search-input"))
-                .first()
-                .await?;
-            lang_search_elem.send_keys(language).await?;
-            let lang_found_elem = driver
-                .query(By::Css(".active-result.highlighted"))
-                .first()
-                .await?;
-            lang_found_elem.click().await?;
+    /// Counts the submissions of the given user on the given problem by walking through the status pages.
+    /// Returns None for contest problems, which have no status page to count on.
+    pub(crate) fn count_submissions(
+        &self,
+        problem_id: &ProblemId,
+        username: &str,
+    ) -> anyhow::Result<Option<usize>> {
+        let driver = driver_or_forward!(self, "count_submissions", problem_id, username);
+        with_async_runtime(async {
+            let Some(mut status_page) = problem_id.status_url(username) else {
+                return Ok(None);
+            };
+            let mut count = 0;
+            loop {
+                driver.get(status_page).await?;
+                count += driver
+                    .find_all(By::Css("#status-table tbody tr"))
+                    .await?
+                    .len();
+                let next_elem = driver.query(By::Id("next_page")).first_opt().await?;
+                let next_page = match next_elem {
+                    Some(elem) => elem.prop("href").await?,
+                    None => None,
+                };
+                let Some(next_page) = next_page else {
+                    break;
+                };
+                status_page = next_page;
+            }
+            Ok(Some(count))
+        })
+    }
+
+    /// Reads up to `limit` latest submissions of the user on the problem from the status page.
+    pub(crate) fn get_submissions(
+        &self,
+        problem_id: &ProblemId,
+        username: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<StatusRow>> {
+        let driver = driver_or_forward!(self, "get_submissions", problem_id, username, limit);
+        with_async_runtime(async {
+            let Some(mut status_page) = problem_id.status_url(username) else {
+                anyhow::bail!("Status page is not available for contest problems");
+            };
+            let mut rows = vec![];
+            while rows.len() < limit {
+                driver.get(status_page).await?;
+                let page_rows = driver
+                    .execute(
+                        r#"
+                        return Array.from(document.querySelectorAll('#status-table tbody tr')).map(tr => {
+                            const cells = tr.querySelectorAll('td');
+                            const text = (elem) => elem?.textContent.trim() ?? '';
+                            const result = tr.querySelector('.result-text');
+                            return [
+                                text(cells[0]),
+                                text(result),
+                                result?.className ?? '',
+                                text(tr.querySelector('.time')),
+                                text(tr.querySelector('.memory')),
+                                text(cells[6]),
+                                text(cells[7]),
+                                tr.querySelector('[data-timestamp]')?.dataset.timestamp ?? '',
+                            ];
+                        });
+                        "#,
+                        vec![],
+                    )
+                    .await?
+                    .convert::<Vec<(String, String, String, String, String, String, String, String)>>()?;
+                rows.extend(page_rows.into_iter().map(
+                    |(id, text, class, time, memory, lang, length, timestamp)| StatusRow {
+                        submission_id: id,
+                        status: SubmissionStatus {
+                            text,
+                            class,
+                            time: leading_number(&time),
+                            memory: leading_number(&memory),
+                        },
+                        lang,
+                        length: leading_number(&length),
+                        timestamp: leading_number(&timestamp),
+                    },
+                ));
+                let next_elem = driver.query(By::Id("next_page")).first_opt().await?;
+                let next_page = match next_elem {
+                    Some(elem) => elem.prop("href").await?,
+                    None => None,
+                };
+                let Some(next_page) = next_page else {
+                    break;
+                };
+                status_page = next_page;
+            }
+            rows.truncate(limit);
+            Ok(rows)
+        })
+    }
+
+    /// Downloads the source of the given submission. Only works for submissions the session can see.
+    /// Fails instead of returning a web page, e.g. when the session expired and the download is redirected to /login.
+    pub(crate) fn get_source(&self, submission_id: &str) -> anyhow::Result<String> {
+        let driver = driver_or_forward!(self, "get_source", submission_id);
+        with_async_runtime(async {
+            let download = driver
+                .execute(
+                    r#"
+                    const path = `/source/download/${arguments[0]}`;
+                    return fetch(path)
+                        .then(res => {
+                            const url = new URL(res.url);
+                            if (res.redirected || url.pathname !== path) return { error: `Redirected to ${url.pathname}` };
+                            if (!res.ok) return { error: `HTTP ${res.status}` };
+                            if ((res.headers.get('Content-Type') ?? '').includes('text/html')) {
+                                return { error: 'Got a web page instead of the source' };
+                            }
+                            return res.text().then(source => ({ source }));
+                        })
+                        .catch(err => ({ error: String(err) }));
+                    "#,
+                    vec![serde_json::to_value(submission_id)?],
+                )
+                .await?
+                .convert::<serde_json::Value>()?;
+            let source = match download["source"].as_str() {
+                Some(source) if !is_html_page(source) => source.to_string(),
+                Some(_) => anyhow::bail!(
+                    "Source of submission #{} is not available: Got a web page instead of the source",
+                    submission_id
+                ),
+                None => anyhow::bail!(
+                    "Source of submission #{} is not available: {}",
+                    submission_id,
+                    download["error"].as_str().unwrap_or("Unknown error")
+                ),
+            };
+            Ok(source)
+        })
+    }
 
-            // Set source: https://stackoverflow.com/a/57621139/4595904 simplified
-            // `send_keys` is incorrect, as bracket/quote matching will be triggered as the source code is typed,
-            // resulting in CE (https://www.acmicpc.net/source/78678130)
-            // Clipboard API seems to require user permission, so inject the string to CodeMirror instance
-            driver
+    /// On submit page, returns the languages the problem can be submitted in.
+    /// Returns an empty list if the submit page is not available (e.g. not logged in).
+    pub(crate) fn get_languages(&self, problem_id: &ProblemId) -> anyhow::Result<Vec<Language>> {
+        let driver = driver_or_forward!(self, "get_languages", problem_id);
+        with_async_runtime(async {
+            let submit_page = problem_id.submit_url();
+            driver.get(submit_page).await?;
+            // The select element is hidden behind the chosen dropdown, so read the options via JS
+            let languages = driver
                 .execute(
-                    "document.querySelector('.CodeMirror').CodeMirror.setValue(arguments[0])",
-                    vec![serde_json::to_value(source)?],
+                    "return Array.from(document.querySelectorAll('#language option')).map(o => [o.value, o.textContent.trim()])",
+                    vec![],
                 )
-                .await?;
+                .await?
+                .convert::<Vec<(String, String)>>()?;
+            Ok(languages
+                .into_iter()
+                .map(|(id, name)| Language { id, name })
+                .collect())
+        })
+    }
 
-            // Submit and wait until refresh starts
-            let submit_elem = driver.query(By::Id("submit_button")).first().await?;
-            submit_elem.click().await?;
-            submit_elem.wait_until().stale().await?;
-            Ok(())
+    /// Fetches the status page of the given submission in the background, without leaving the current page.
+    pub(crate) fn get_submission_status(
+        &self,
+        submission_id: &str,
+    ) -> anyhow::Result<SubmissionStatus> {
+        let driver = driver_or_forward!(self, "get_submission_status", submission_id);
+        with_async_runtime(async {
+            let row = driver
+                .execute(
+                    r#"
+                    const id = arguments[0];
+                    return fetch(`/status?top=${id}`).then(res => res.text()).then(html => {
+                        const doc = new DOMParser().parseFromString(html, 'text/html');
+                        const row = doc.getElementById(`solution-${id}`);
+                        const result = row && row.querySelector('.result-text');
+                        if (!result) return null;
+                        const text = (selector) => row.querySelector(selector)?.textContent.trim() ?? '';
+                        return [result.textContent.trim(), result.className, text('.time'), text('.memory')];
+                    });
+                    "#,
+                    vec![serde_json::to_value(submission_id)?],
+                )
+                .await?
+                .convert::<Option<(String, String, String, String)>>()?;
+            let Some((text, class, time, memory)) = row else {
+                anyhow::bail!("Submission #{} not found", submission_id);
+            };
+            Ok(SubmissionStatus {
+                text,
+                class,
+                time: leading_number(&time),
+                memory: leading_number(&memory),
+            })
         })
     }
 
-    /// On submission status page, returns (status text, status class).
-    pub(crate) fn get_submission_status(&self) -> anyhow::Result<(String, String)> {
+    /// Asks the JSON status channel used by the live updates of the status page.
+    /// Returns None if the response cannot be shown as a status, and an error if the channel is unavailable.
+    pub(crate) fn get_submission_status_json(
+        &self,
+        submission_id: &str,
+    ) -> anyhow::Result<Option<SubmissionStatus>> {
+        let driver = driver_or_forward!(self, "get_submission_status_json", submission_id);
         with_async_runtime(async {
-            let driver = &self.webdriver;
-            let elem_status = driver.query(By::ClassName("result-text")).first().await?;
-            let status = elem_status.text().await?.to_string();
-            let class = elem_status.class_name().await?.unwrap_or(String::new());
-            Ok((status, class))
+            let data = driver
+                .execute(
+                    r#"
+                    const body = new URLSearchParams({ solution_id: arguments[0] });
+                    return fetch('/status/ajax', {
+                        method: 'POST',
+                        body,
+                        headers: { 'X-Requested-With': 'XMLHttpRequest' },
+                    })
+                        .then(res => res.ok ? res.json() : null)
+                        .catch(() => null);
+                    "#,
+                    vec![serde_json::to_value(submission_id)?],
+                )
+                .await?
+                .convert::<serde_json::Value>()?;
+            if data.is_null() {
+                anyhow::bail!("JSON status channel unavailable");
+            }
+            Ok(SubmissionStatus::from_status_json(&data))
         })
     }
 
     pub(crate) fn screenshot(&self) -> anyhow::Result<()> {
+        let driver = driver_or_forward!(self, "screenshot");
         with_async_runtime(async {
-            let driver = &self.webdriver;
             driver.screenshot(Path::new("./screenshot.png")).await?;
             Ok(())
         })
     }
 
     pub(crate) fn source(&self) -> anyhow::Result<String> {
+        let driver = driver_or_forward!(self, "source");
         with_async_runtime(async {
-            let driver = &self.webdriver;
             let source = driver.source().await?;
             Ok(source)
         })
     }
 
-    /// Gracefully terminate the browser. Should be called even on error.
+    /// Gracefully terminate the browser and the driver started for it. Should be called even on error.
+    /// A browser owned by the daemon is kept running.
     pub(crate) fn quit(self) -> anyhow::Result<()> {
-        with_async_runtime(async {
-            self.webdriver.quit().await?;
-            if cfg!(target_os = "windows") {
-                run_silent("for /f tokens^=2 %a in ('tasklist ^| findstr geckodriver') do @taskkill /PID %a /F").ok();
-            } else {
-                run_silent("kill $(pidof geckodriver)").ok();
+        let Backend::Local {
+            webdriver,
+            driver,
+            session_file,
+        } = self.backend
+        else {
+            return Ok(());
+        };
+        let result = with_async_runtime(async {
+            // Keep the cookies refreshed during the session for the next run
+            if let Some(path) = &session_file {
+                if let Err(err) = save_session(&webdriver, path).await {
+                    report_notice(&format!("Failed to save the session: {}", err));
+                }
             }
+            webdriver.quit().await?;
             Ok(())
-        })
+        });
+        // Only the driver started by this session is killed; an external WebDriver server is left running
+        if let Some(driver) = driver {
+            driver.kill();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boj_cookies_by_domain() {
+        let cookie = |domain: Option<&str>| {
+            let mut cookie = Cookie::new("name", "value");
+            cookie.domain = domain.map(str::to_string);
+            cookie
+        };
+        assert!(is_boj_cookie(&cookie(Some(".acmicpc.net"))));
+        assert!(is_boj_cookie(&cookie(Some("www.acmicpc.net"))));
+        assert!(!is_boj_cookie(&cookie(Some("localhost"))));
+        assert!(!is_boj_cookie(&cookie(Some("notacmicpc.net"))));
+        assert!(!is_boj_cookie(&cookie(None)));
+    }
+
+    #[test]
+    fn login_fails_without_remember_me_cookie() {
+        let cookie = |name: &str| Some(Cookie::new(name, format!("{}-value", name)));
+        let credentials = login_credentials(cookie("bojautologin"), cookie("OnlineJudge"), "alice")
+            .unwrap()
+            .unwrap();
+        assert_eq!(credentials.bojautologin, "bojautologin-value");
+        assert_eq!(credentials.onlinejudge, "OnlineJudge-value");
+        assert_eq!(credentials.username.as_deref(), Some("alice"));
+        // the login page is shown again after a rejected login, whether or not a session started
+        assert!(login_credentials(None, cookie("OnlineJudge"), "alice")
+            .unwrap()
+            .is_none());
+        assert!(login_credentials(None, None, "alice").unwrap().is_none());
+        assert!(login_credentials(cookie("bojautologin"), None, "alice").is_err());
+    }
+
+    #[test]
+    fn mock_login_page_has_login_form_fields() {
+        // examples/mock_login.rs stands in for the real login page:
+        // it must show every field the login fills in and read each one from the submitted form
+        let mock = include_str!("../../examples/mock_login.rs");
+        for field in [
+            LOGIN_USERNAME_FIELD,
+            LOGIN_PASSWORD_FIELD,
+            LOGIN_REMEMBER_FIELD,
+        ] {
+            assert!(mock.contains(&format!(r#"name="{}""#, field)), "{}", field);
+            assert!(mock.contains(&format!(r#""{}")"#, field)), "{}", field);
+        }
+        assert!(mock.contains(&format!(r#"id="{}""#, LOGIN_SUBMIT_BUTTON)));
     }
 }
@@ -0,0 +1,406 @@
+use crate::data::BundleConfig;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Bundles the given solution into a single source file according to the config.
+/// Rust files get the configured crates inlined as modules, and C/C++ files get quoted includes inlined.
+/// Returns None if bundling does not apply to the file.
+pub(crate) fn bundle(
+    file: &Path,
+    source: &str,
+    config: &BundleConfig,
+) -> anyhow::Result<Option<String>> {
+    let ext = file
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match &ext[..] {
+        "rs" if !config.crates.is_empty() => bundle_rust(source, &config.crates).map(Some),
+        "c" | "cc" | "cpp" | "cxx" | "c++" | "h" | "hh" | "hpp" => {
+            let include_dirs = config
+                .include_dirs
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>();
+            bundle_cpp(file, source, &include_dirs).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Finds the index of the brace matching the one at `open`, skipping comments and string/char literals.
+fn matching_brace(s: &str, open: usize) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut depth = 0usize;
+    let mut i = open;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i + 1 < bytes.len() && !(bytes[i] == b'*' && bytes[i + 1] == b'/') {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b'r' if matches!(bytes.get(i + 1), Some(b'"' | b'#'))
+                && (i == 0 || !bytes[i - 1].is_ascii_alphanumeric() && bytes[i - 1] != b'_') =>
+            {
+                // raw string: r"..." or r#"..."#
+                let hashes = bytes[i + 1..].iter().take_while(|&&b| b == b'#').count();
+                if bytes.get(i + 1 + hashes) == Some(&b'"') {
+                    let closing = format!("\"{}", "#".repeat(hashes));
+                    let body_start = i + 2 + hashes;
+                    i = s[body_start..]
+                        .find(&closing)
+                        .map_or(bytes.len(), |pos| body_start + pos + closing.len() - 1);
+                }
+            }
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'\'' => {
+                // char literal; lifetimes and labels are left alone
+                if bytes.get(i + 1) == Some(&b'\\') {
+                    while i + 1 < bytes.len() && bytes[i + 1] != b'\'' {
+                        i += 1;
+                    }
+                    i += 1;
+                } else if let Some(c) = s[i + 1..].chars().next() {
+                    if s[i + 1 + c.len_utf8()..].starts_with('\'') {
+                        i += c.len_utf8() + 1;
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Removes `#[cfg(test)]` modules, which are never part of a submission.
+fn strip_test_modules(source: &str) -> String {
+    static CFG_TEST_MOD: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"#\[cfg\(test\)\]\s*(?:#\[[^\]]*\]\s*)*(?:pub(?:\([^)]*\))?\s+)?mod\s+[A-Za-z_][A-Za-z0-9_]*\s*",
+        )
+        .unwrap()
+    });
+    let mut source = source.to_string();
+    let mut search_from = 0;
+    while let Some(m) = CFG_TEST_MOD.find_at(&source, search_from) {
+        let end = match source[m.end()..].chars().next() {
+            Some(';') => Some(m.end()),
+            Some('{') => matching_brace(&source, m.end()),
+            _ => None,
+        };
+        match end {
+            Some(end) => source.replace_range(m.start()..=end, ""),
+            None => search_from = m.end(),
+        }
+    }
+    source
+}
+
+static MOD_DECL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(\s*)((?:pub(?:\([^)]*\))?\s+)?)mod\s+([A-Za-z_][A-Za-z0-9_]*)\s*;\s*$").unwrap()
+});
+
+/// Reads a Rust source file and recursively inlines its `mod foo;` declarations.
+fn expand_rust_file(path: &Path) -> anyhow::Result<String> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        anyhow::bail!("Failed to read `{}`", path.display());
+    };
+    expand_rust_modules(path, &strip_test_modules(&content))
+}
+
+/// Inlines the `mod foo;` declarations in `content`, which is the content of the file at `path`.
+fn expand_rust_modules(path: &Path, content: &str) -> anyhow::Result<String> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let is_mod_root = path
+        .file_name()
+        .is_some_and(|name| name == "lib.rs" || name == "main.rs" || name == "mod.rs");
+    let base_dir = if is_mod_root {
+        dir.to_path_buf()
+    } else {
+        dir.join(path.file_stem().unwrap_or_default())
+    };
+    let mut lines: Vec<String> = vec![];
+    for line in content.lines() {
+        let Some(caps) = MOD_DECL.captures(line) else {
+            lines.push(line.to_string());
+            continue;
+        };
+        let (indent, vis, name) = (&caps[1], &caps[2], &caps[3]);
+        let candidates = [
+            base_dir.join(format!("{}.rs", name)),
+            base_dir.join(name).join("mod.rs"),
+        ];
+        let Some(module_path) = candidates.iter().find(|p| p.is_file()) else {
+            anyhow::bail!(
+                "File for module `{}` not found in `{}`",
+                name,
+                base_dir.display()
+            );
+        };
+        let module = expand_rust_file(module_path)?;
+        lines.push(format!("{}{}mod {} {{", indent, vis, name));
+        lines.extend(module.lines().map(|l| l.to_string()));
+        lines.push(format!("{}}}", indent));
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Inlines each crate used by the solution as a module at the end of the source.
+/// All modules of the crate are kept, as macros, trait impls and re-exports can be needed
+/// without the module ever being named.
+fn bundle_rust(source: &str, crates: &HashMap<String, String>) -> anyhow::Result<String> {
+    let mut used_crates = vec![];
+    for name in crates.keys() {
+        if Regex::new(&format!(r"\b{}::", regex::escape(name)))?.is_match(source) {
+            used_crates.push(name);
+        }
+    }
+    if used_crates.is_empty() {
+        return Ok(source.to_string());
+    }
+    used_crates.sort();
+    static EXTERN_CRATE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^\s*extern\s+crate\s+([A-Za-z_][A-Za-z0-9_]*)\s*;").unwrap());
+    let mut bundled = source
+        .lines()
+        .filter(|line| {
+            EXTERN_CRATE
+                .captures(line)
+                .is_none_or(|caps| !used_crates.iter().any(|name| **name == caps[1]))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    bundled += "\n";
+    for name in used_crates {
+        let lib_path = Path::new(&crates[name]);
+        let Ok(lib) = std::fs::read_to_string(lib_path) else {
+            anyhow::bail!(
+                "Failed to read `{}` for crate `{}`",
+                lib_path.display(),
+                name
+            );
+        };
+        let lib = expand_rust_modules(lib_path, &strip_test_modules(&lib))?;
+        // paths inside the crate now start from the module
+        static CRATE_PATH: Lazy<Regex> = Lazy::new(|| Regex::new(r"\bcrate::").unwrap());
+        let lib = CRATE_PATH.replace_all(&lib, format!("crate::{}::", name));
+        bundled += &format!("\npub mod {} {{\n{}}}\n", name, lib);
+    }
+    Ok(bundled)
+}
+
+/// Recursively inlines `#include "..."` files found next to the including file or in the include dirs.
+/// Each file is inlined at most once, and `#pragma once` is removed from inlined files.
+fn bundle_cpp(file: &Path, source: &str, include_dirs: &[PathBuf]) -> anyhow::Result<String> {
+    let mut included = HashSet::new();
+    if let Ok(canonical) = file.canonicalize() {
+        included.insert(canonical);
+    }
+    let mut out = String::new();
+    expand_cpp(file, source, include_dirs, &mut included, true, &mut out)?;
+    Ok(out)
+}
+
+fn expand_cpp(
+    file: &Path,
+    source: &str,
+    include_dirs: &[PathBuf],
+    included: &mut HashSet<PathBuf>,
+    is_root: bool,
+    out: &mut String,
+) -> anyhow::Result<()> {
+    static INCLUDE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r#"^\s*#\s*include\s*"([^"]+)""#).unwrap());
+    static PRAGMA_ONCE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*#\s*pragma\s+once").unwrap());
+    let dir = file.parent().unwrap_or(Path::new(""));
+    for line in source.lines() {
+        if !is_root && PRAGMA_ONCE.is_match(line) {
+            continue;
+        }
+        let Some(caps) = INCLUDE.captures(line) else {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        };
+        let header = &caps[1];
+        let found = std::iter::once(dir)
+            .chain(include_dirs.iter().map(|d| d.as_path()))
+            .map(|d| d.join(header))
+            .find(|p| p.is_file());
+        let Some(header_path) = found else {
+            // not a local header; leave it to the compiler
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        };
+        if !included.insert(header_path.canonicalize()?) {
+            continue;
+        }
+        let content = std::fs::read_to_string(&header_path)?;
+        expand_cpp(&header_path, &content, include_dirs, included, false, out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an empty directory for the files of one test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("gaboja-bundler-{}-{}", std::process::id(), name));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn matching_brace_skips_nested_braces() {
+        let s = "fn f() { if x { y } else { z } } rest";
+        assert_eq!(matching_brace(s, 7), Some(31));
+        assert_eq!(matching_brace(s, 14), Some(18));
+    }
+
+    #[test]
+    fn matching_brace_skips_comments_and_literals() {
+        let s = r##"{ "}" '}' '\'' // }
+/* } */ r#"}"# 'a }"##;
+        assert_eq!(matching_brace(s, 0), Some(s.len() - 1));
+    }
+
+    #[test]
+    fn matching_brace_unbalanced() {
+        assert_eq!(matching_brace("{ { }", 0), None);
+    }
+
+    #[test]
+    fn strip_test_modules_removes_inline_and_file_modules() {
+        let source = "\
+fn main() {}
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn t() { let _ = \"}\"; }
+}
+#[cfg(test)]
+#[allow(dead_code)]
+pub(crate) mod helpers;
+fn after() {}
+";
+        assert_eq!(
+            strip_test_modules(source),
+            "fn main() {}\n\n\nfn after() {}\n"
+        );
+    }
+
+    #[test]
+    fn strip_test_modules_keeps_other_cfg() {
+        let source = "#[cfg(not(test))]\nmod real {}\n";
+        assert_eq!(strip_test_modules(source), source);
+    }
+
+    #[test]
+    fn expand_rust_modules_inlines_files() {
+        let dir = test_dir("expand-rust");
+        std::fs::create_dir_all(dir.join("graph")).unwrap();
+        std::fs::write(dir.join("math.rs"), "pub fn gcd() {}\n").unwrap();
+        std::fs::write(dir.join("graph/mod.rs"), "pub mod dfs;\n").unwrap();
+        std::fs::write(
+            dir.join("graph/dfs.rs"),
+            "pub fn dfs() {}\n#[cfg(test)]\nmod tests {}\n",
+        )
+        .unwrap();
+        let lib = "pub mod math;\n    mod graph;\nfn top() {}\n";
+        let expanded = expand_rust_modules(&dir.join("lib.rs"), lib).unwrap();
+        assert_eq!(
+            expanded,
+            "\
+pub mod math {
+pub fn gcd() {}
+}
+    mod graph {
+pub mod dfs {
+pub fn dfs() {}
+
+}
+    }
+fn top() {}
+"
+        );
+    }
+
+    #[test]
+    fn expand_rust_modules_resolves_non_root_files() {
+        let dir = test_dir("expand-rust-nested");
+        std::fs::create_dir_all(dir.join("util")).unwrap();
+        std::fs::write(dir.join("util/io.rs"), "pub fn read() {}\n").unwrap();
+        let expanded = expand_rust_modules(&dir.join("util.rs"), "pub mod io;\n").unwrap();
+        assert_eq!(expanded, "pub mod io {\npub fn read() {}\n}\n");
+    }
+
+    #[test]
+    fn expand_rust_modules_missing_file() {
+        let dir = test_dir("expand-rust-missing");
+        assert!(expand_rust_modules(&dir.join("lib.rs"), "mod nowhere;\n").is_err());
+    }
+
+    #[test]
+    fn expand_cpp_inlines_local_headers_once() {
+        let dir = test_dir("expand-cpp");
+        std::fs::create_dir_all(dir.join("include")).unwrap();
+        std::fs::write(dir.join("a.h"), "#pragma once\n#include \"b.h\"\nint a;\n").unwrap();
+        std::fs::write(dir.join("include/b.h"), "#pragma once\nint b;\n").unwrap();
+        let source =
+            "#pragma once\n#include <cstdio>\n#include \"a.h\"\n#include \"b.h\"\nint main() {}\n";
+        let bundled = bundle_cpp(&dir.join("main.cpp"), source, &[dir.join("include")]).unwrap();
+        assert_eq!(
+            bundled,
+            "#pragma once\n#include <cstdio>\nint b;\nint a;\nint main() {}\n"
+        );
+    }
+
+    #[test]
+    fn expand_cpp_leaves_unknown_headers() {
+        let dir = test_dir("expand-cpp-unknown");
+        let mut included = HashSet::new();
+        let mut out = String::new();
+        let source = "#include \"missing.h\"\n";
+        expand_cpp(
+            &dir.join("main.cpp"),
+            source,
+            &[],
+            &mut included,
+            false,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(out, source);
+    }
+}
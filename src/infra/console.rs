@@ -1,11 +1,64 @@
+use crate::data::{is_judging, verdict_from_class, Problem, Stats, StatusRow, SubmissionRecord};
+use crate::infra::credentials::mask_secrets;
 use crate::infra::subprocess::Output;
 use console::{measure_text_width, pad_str, style, Alignment};
-use indicatif::{ProgressBar, ProgressStyle};
+use dialoguer::theme::{ColorfulTheme, Theme};
+use dialoguer::{BasicHistory, History};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use once_cell::sync::Lazy;
 use regex::Regex;
+use serde_json::json;
 use similar::ChangeTag;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use std::time::Duration;
 
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Switches the reports between colored text and one JSON object per line.
+pub(crate) fn set_json_output(json: bool) {
+    JSON_OUTPUT.store(json, Ordering::Relaxed);
+}
+
+pub(crate) fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+pub(crate) type EventSink = Box<dyn Fn(serde_json::Value) + Send>;
+
+static EVENT_SINK: Mutex<Option<EventSink>> = Mutex::new(None);
+
+/// Sends the events somewhere other than stdout, e.g. to a connected editor. `None` restores stdout.
+pub(crate) fn set_event_sink(sink: Option<EventSink>) {
+    *EVENT_SINK.lock().unwrap() = sink;
+}
+
+/// Prints an event as a single line of JSON, with its name in the `event` field.
+pub(crate) fn emit(event: &str, fields: serde_json::Value) {
+    let mut object = serde_json::Map::new();
+    object.insert("event".to_string(), event.into());
+    if let serde_json::Value::Object(fields) = fields {
+        object.extend(fields);
+    }
+    let object = serde_json::Value::Object(object);
+    match &*EVENT_SINK.lock().unwrap() {
+        Some(sink) => sink(object),
+        None => println!("{}", object),
+    }
+}
+
+/// Progress bars draw nothing in JSON mode; the events are emitted instead.
+fn progress_bar(len: Option<u64>) -> ProgressBar {
+    if json_output() {
+        ProgressBar::with_draw_target(len, ProgressDrawTarget::hidden())
+    } else {
+        match len {
+            Some(len) => ProgressBar::new(len),
+            None => ProgressBar::new_spinner(),
+        }
+    }
+}
+
 pub(crate) struct Spinner {
     progress_bar: ProgressBar,
 }
@@ -13,7 +66,7 @@ pub(crate) struct Spinner {
 impl Spinner {
     pub(crate) fn new(msg: &str) -> Self {
         let style = ProgressStyle::with_template("{spinner} {msg}").unwrap();
-        let spinner = ProgressBar::new_spinner().with_style(style);
+        let spinner = progress_bar(None).with_style(style);
         spinner.enable_steady_tick(Duration::from_millis(100));
         spinner.set_message(msg.to_string());
         Self {
@@ -26,6 +79,9 @@ impl Spinner {
     }
 
     pub(crate) fn finish(self, msg: &str) {
+        if json_output() {
+            emit("message", json!({ "ok": true, "message": msg }));
+        }
         let style = ProgressStyle::with_template("{prefix} {msg}").unwrap();
         self.progress_bar.set_style(style);
         self.progress_bar
@@ -34,6 +90,9 @@ impl Spinner {
     }
 
     pub(crate) fn abandon(self, msg: &str) {
+        if json_output() {
+            emit("message", json!({ "ok": false, "message": msg }));
+        }
         let style = ProgressStyle::with_template("{prefix} {msg}").unwrap();
         self.progress_bar.set_style(style);
         self.progress_bar
@@ -59,7 +118,7 @@ impl TestProgress {
     pub(crate) fn new(len: u64) -> Self {
         let style =
             ProgressStyle::with_template("[{pos:>2}/{len:>2}] {msg}\n{bar:40.green}").unwrap();
-        let progress_bar = ProgressBar::new(len).with_style(style);
+        let progress_bar = progress_bar(Some(len)).with_style(style);
         progress_bar.set_message("Running sample tests...".to_string());
         progress_bar.set_position(1);
         Self { progress_bar }
@@ -73,6 +132,9 @@ impl TestProgress {
         output: Option<Output>,
         diff: bool,
     ) -> bool {
+        if json_output() {
+            return self.emit_test_result(stdin, expected, output, diff);
+        }
         let fail_style =
             ProgressStyle::with_template("[{pos:>2}/{len:>2}] {msg}\n{bar:40.red}").unwrap();
         let ac = console::style("AC".to_string()).green();
@@ -93,12 +155,6 @@ impl TestProgress {
         }) = output
         {
             let duration = duration.as_secs_f64();
-            fn trim_lines(s: &str) -> String {
-                s.trim_end()
-                    .lines()
-                    .flat_map(|l| [l.trim_end(), "\n"])
-                    .collect()
-            }
             let stdin = trim_lines(stdin);
             let expected = trim_lines(expected);
             let stdout = trim_lines(&stdout);
@@ -160,6 +216,68 @@ impl TestProgress {
     }
 }
 
+impl TestProgress {
+    fn emit_test_result(
+        &self,
+        stdin: &str,
+        expected: &str,
+        output: Option<Output>,
+        diff: bool,
+    ) -> bool {
+        let case = self.progress_bar.position();
+        let expected = trim_lines(expected);
+        let mut event = json!({
+            "case": case,
+            "total": self.progress_bar.length(),
+            "input": trim_lines(stdin),
+            "expected": expected,
+        });
+        let passed = if let Some(Output {
+            stdout,
+            stderr,
+            success,
+            duration,
+        }) = output
+        {
+            let stdout = trim_lines(&stdout);
+            let verdict = if !success {
+                "RE"
+            } else if !diff {
+                "OK"
+            } else if expected == stdout {
+                "AC"
+            } else {
+                event["diff"] = similar::TextDiff::from_lines(&expected, &stdout)
+                    .unified_diff()
+                    .header("expected", "output")
+                    .to_string()
+                    .into();
+                "WA"
+            };
+            event["verdict"] = verdict.into();
+            event["time"] = duration.as_secs_f64().into();
+            event["output"] = stdout.into();
+            event["stderr"] = trim_lines(&stderr).into();
+            verdict == "AC" || verdict == "OK"
+        } else {
+            event["verdict"] = "TLE".into();
+            false
+        };
+        emit("test", event);
+        if passed {
+            self.progress_bar.inc(1);
+        }
+        passed
+    }
+}
+
+fn trim_lines(s: &str) -> String {
+    s.trim_end()
+        .lines()
+        .flat_map(|l| [l.trim_end(), "\n"])
+        .collect()
+}
+
 impl Drop for TestProgress {
     fn drop(&mut self) {
         if !self.progress_bar.is_finished() {
@@ -175,23 +293,15 @@ pub(crate) struct SubmitProgress {
 impl SubmitProgress {
     pub(crate) fn new() -> Self {
         let style = ProgressStyle::with_template("{msg}\n{bar:40.green}").unwrap();
-        let progress_bar = ProgressBar::new(100).with_style(style);
+        let progress_bar = progress_bar(Some(100)).with_style(style);
         progress_bar.set_message("Waiting for response...".to_string());
         Self { progress_bar }
     }
 
     /// Returns true if finished
     pub(crate) fn update(&self, status_text: &str, status_class: &str) -> bool {
-        static CONTINUE_CLASS: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(
-                r"result-wait|result-rejudge-wait|result-no-judge|result-compile|result-judging",
-            )
-            .unwrap()
-        });
-        static CONTINUE_TEXT: Lazy<Regex> =
-            Lazy::new(|| Regex::new(r"채점|중|Pending|Judg").unwrap());
         static PERCENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"\((\d+)%\)").unwrap());
-        if CONTINUE_CLASS.is_match(status_class) || CONTINUE_TEXT.is_match(status_text) {
+        if is_judging(status_text, status_class) {
             // one of them can update first, so keep updating until both are decisive
             self.progress_bar.set_message(status_text.to_string());
             if let Some(capture) = PERCENT.captures(status_text) {
@@ -202,29 +312,15 @@ impl SubmitProgress {
         }
         let fail_style = ProgressStyle::with_template("{msg}\n{bar:40.red}").unwrap();
         let semifail_style = ProgressStyle::with_template("{msg}\n{bar:40.yellow}").unwrap();
-        static RESULT: Lazy<Regex> = Lazy::new(|| Regex::new(r" result-([a-z]+)").unwrap());
-        let result = RESULT
-            .captures(status_class)
-            .unwrap()
-            .get(1)
-            .unwrap()
-            .as_str()
-            .to_ascii_uppercase();
-        let mut color_result = console::style(if result == "RTE" {
-            "RE".to_string()
-        } else {
-            result.clone()
-        });
+        let result = verdict_from_class(status_class).unwrap_or_else(|| "?".to_string());
         if result == "AC" {
-            color_result = color_result.green();
             self.progress_bar.set_position(100);
         } else if result == "PAC" {
-            color_result = color_result.yellow();
             self.progress_bar.set_style(semifail_style);
         } else {
-            color_result = color_result.red();
             self.progress_bar.set_style(fail_style);
         }
+        let color_result = style_verdict(&result);
         self.progress_bar
             .abandon_with_message(format!("{} [{}]", status_text, color_result));
         true
@@ -243,20 +339,393 @@ impl Drop for SubmitProgress {
 }
 
 pub(crate) fn report_stdin(stdin: &str) {
+    if json_output() {
+        emit("output", json!({ "stream": "stdin", "text": stdin }));
+        return;
+    }
     let header = console::style("STDIN:".to_string()).yellow();
     println!("{}\n{}", header, stdin);
 }
 
 pub(crate) fn report_stdout(stdout: &str) {
+    if json_output() {
+        emit("output", json!({ "stream": "stdout", "text": stdout }));
+        return;
+    }
     let header = console::style("STDOUT:".to_string()).yellow();
     println!("{}\n{}", header, stdout);
 }
 
 pub(crate) fn report_stderr(stderr: &str) {
+    if json_output() {
+        emit("output", json!({ "stream": "stderr", "text": stderr }));
+        return;
+    }
     let header = console::style("STDERR:".to_string()).yellow();
     println!("{}\n{}", header, stderr);
 }
 
+fn style_verdict(verdict: &str) -> console::StyledObject<&str> {
+    match verdict {
+        "AC" => style(verdict).green(),
+        "PAC" => style(verdict).yellow(),
+        "?" => style(verdict).dim(),
+        _ => style(verdict).red(),
+    }
+}
+
+/// Reports the status of a submission still being judged. Only emitted in JSON mode,
+/// where `SubmitProgress` draws nothing.
+pub(crate) fn report_submission_progress(record: &SubmissionRecord) {
+    static PERCENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"\((\d+)%\)").unwrap());
+    if !json_output() {
+        return;
+    }
+    let progress = PERCENT
+        .captures(&record.status)
+        .and_then(|caps| caps[1].parse::<u64>().ok());
+    emit(
+        "submission_progress",
+        json!({
+            "id": record.submission_id,
+            "problem": record.problem,
+            "status": record.status,
+            "progress": progress,
+        }),
+    );
+}
+
+/// Reports the final result of a submission judged in the background.
+pub(crate) fn report_submission_result(record: &SubmissionRecord) {
+    if json_output() {
+        emit(
+            "submission_result",
+            json!({
+                "id": record.submission_id,
+                "problem": record.problem,
+                "title": record.title,
+                "lang": record.lang,
+                "status": record.status,
+                "verdict": record.verdict,
+                "time": record.time,
+                "memory": record.memory,
+                "score": record.score,
+            }),
+        );
+        return;
+    }
+    let verdict = record.verdict.as_deref().unwrap_or("?");
+    let mark = if verdict == "AC" {
+        style("✔").green()
+    } else {
+        style("✘").red()
+    };
+    let mut usage = String::new();
+    if let (Some(memory), Some(time)) = (record.memory, record.time) {
+        usage = format!(" {}KB {}ms", memory, time);
+    }
+    println!(
+        "\n{} #{} {} {}: {} [{}]{}",
+        mark,
+        record.submission_id.as_deref().unwrap_or("?"),
+        record.problem,
+        record.title,
+        record.status,
+        style_verdict(verdict),
+        usage
+    );
+}
+
+/// Reports a submission whose judging could not be followed to the end.
+pub(crate) fn report_tracking_stopped(record: &SubmissionRecord, reason: &str) {
+    if json_output() {
+        emit(
+            "submission_stopped",
+            json!({
+                "id": record.submission_id,
+                "problem": record.problem,
+                "status": record.status,
+                "reason": reason,
+            }),
+        );
+        return;
+    }
+    println!(
+        "\n{} #{} {} {}: stopped tracking ({}); last status: {}",
+        style("?").yellow(),
+        record.submission_id.as_deref().unwrap_or("?"),
+        record.problem,
+        record.title,
+        reason,
+        record.status
+    );
+}
+
+pub(crate) fn report_problem(problem: &Problem) {
+    if json_output() {
+        emit(
+            "problem",
+            json!({
+                "id": problem.id.to_string(),
+                "title": problem.title,
+                "time": problem.time,
+                "time_bonus": problem.time_bonus,
+                "memory": problem.memory,
+                "memory_bonus": problem.memory_bonus,
+                "samples": problem.io.len(),
+            }),
+        );
+        return;
+    }
+    println!("Problem {} {}", problem.id, problem.title);
+    println!(
+        "Time limit: {:.3}s{} / Memory limit: {}MB{}",
+        problem.time,
+        if !problem.time_bonus {
+            " (No bonus)"
+        } else {
+            ""
+        },
+        problem.memory,
+        if !problem.memory_bonus {
+            " (No bonus)"
+        } else {
+            ""
+        }
+    );
+}
+
+/// Reports the result of a build. The error output is shown only on failure.
+pub(crate) fn report_build(build: &str, error: Option<&str>) {
+    if json_output() {
+        emit(
+            "build",
+            json!({ "command": build, "success": error.is_none(), "stderr": error }),
+        );
+    } else if let Some(error) = error {
+        report_stderr(error);
+    }
+}
+
+/// Reports something the user should know about that is not the result of a command.
+pub(crate) fn report_notice(msg: &str) {
+    if json_output() {
+        emit("message", json!({ "ok": true, "message": msg }));
+    } else {
+        println!("{}", msg);
+    }
+}
+
+pub(crate) fn report_error(err: &dyn std::fmt::Display) {
+    if json_output() {
+        emit("error", json!({ "message": err.to_string() }));
+    } else {
+        println!("Error: {}", err);
+    }
+}
+
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// Lists the tracked submissions with their current status.
+pub(crate) fn report_submissions(records: &[(&SubmissionRecord, u64)]) {
+    if json_output() {
+        let submissions = records
+            .iter()
+            .map(|(record, age)| {
+                json!({
+                    "id": record.submission_id,
+                    "problem": record.problem,
+                    "lang": record.lang,
+                    "status": record.status,
+                    "verdict": record.verdict,
+                    "age": age,
+                })
+            })
+            .collect::<Vec<_>>();
+        emit("submissions", json!({ "submissions": submissions }));
+        return;
+    }
+    if records.is_empty() {
+        println!("No submissions in this session");
+        return;
+    }
+    for (record, age) in records {
+        let status = match &record.verdict {
+            Some(verdict) => format!("{} [{}]", record.status, style_verdict(verdict)),
+            None => style(record.status.clone()).cyan().to_string(),
+        };
+        println!(
+            "{:>10}  {:>8}  {:<16} {:>8}  {}",
+            record.submission_id.as_deref().unwrap_or("?"),
+            record.problem,
+            record.lang,
+            format_age(*age),
+            status
+        );
+    }
+}
+
+/// Prints the submissions read from the status page as a table, latest first.
+pub(crate) fn report_history(rows: &[StatusRow], now: u64) {
+    if json_output() {
+        let rows = rows
+            .iter()
+            .map(|row| {
+                json!({
+                    "id": row.submission_id,
+                    "status": row.status.text,
+                    "verdict": (!is_judging(&row.status.text, &row.status.class))
+                        .then(|| verdict_from_class(&row.status.class))
+                        .flatten(),
+                    "time": row.status.time,
+                    "memory": row.status.memory,
+                    "lang": row.lang,
+                    "length": row.length,
+                    "timestamp": row.timestamp,
+                })
+            })
+            .collect::<Vec<_>>();
+        emit("history", json!({ "now": now, "submissions": rows }));
+        return;
+    }
+    if rows.is_empty() {
+        println!("No submissions found");
+        return;
+    }
+    println!(
+        "{:>10}  {:<7} {:>7} {:>9}  {:<16} {:>7}  {:>8}",
+        "ID", "RESULT", "TIME", "MEMORY", "LANG", "LENGTH", "AGE"
+    );
+    let optional = |value: Option<u64>, unit: &str| match value {
+        Some(value) => format!("{}{}", value, unit),
+        None => "-".to_string(),
+    };
+    for row in rows {
+        let result = if is_judging(&row.status.text, &row.status.class) {
+            style("...").cyan().to_string()
+        } else {
+            let verdict = verdict_from_class(&row.status.class).unwrap_or_else(|| "?".to_string());
+            style_verdict(&verdict).to_string()
+        };
+        let age = match row.timestamp {
+            Some(timestamp) => format_age(now.saturating_sub(timestamp)),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:>10}  {} {:>7} {:>9}  {:<16} {:>7}  {:>8}",
+            row.submission_id,
+            pad_str(&result, 7, Alignment::Left, None),
+            optional(row.status.time, "ms"),
+            optional(row.status.memory, "KB"),
+            row.lang,
+            optional(row.length, "B"),
+            age
+        );
+    }
+}
+
+pub(crate) fn report_stats(stats: &Stats) {
+    fn format_duration(secs: u64) -> String {
+        match secs {
+            0..=59 => format!("{}s", secs),
+            60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+            _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        }
+    }
+    if json_output() {
+        let verdicts = stats
+            .verdicts
+            .iter()
+            .map(|(verdict, count)| json!({ "verdict": verdict, "count": count }))
+            .collect::<Vec<_>>();
+        let time_to_ac = stats
+            .time_to_ac
+            .iter()
+            .map(|(problem, title, secs)| {
+                json!({ "problem": problem, "title": title, "seconds": secs })
+            })
+            .collect::<Vec<_>>();
+        let languages = stats
+            .languages
+            .iter()
+            .map(|(lang, count, ac)| json!({ "lang": lang, "submitted": count, "ac": ac }))
+            .collect::<Vec<_>>();
+        emit(
+            "stats",
+            json!({
+                "loaded": stats.loaded,
+                "attempted": stats.attempted,
+                "solved": stats.solved,
+                "attempts_per_solve": stats.attempts_per_solve,
+                "verdicts": verdicts,
+                "time_to_ac": time_to_ac,
+                "languages": languages,
+            }),
+        );
+        return;
+    }
+    let header = |title: &str| console::style(title.to_string()).yellow();
+    println!(
+        "{} {} loaded, {} attempted, {} solved",
+        header("Problems:"),
+        stats.loaded,
+        stats.attempted,
+        stats.solved
+    );
+    if let Some(attempts) = stats.attempts_per_solve {
+        println!("{} {:.2}", header("Attempts per solve:"), attempts);
+    }
+    if !stats.verdicts.is_empty() {
+        let verdicts = stats
+            .verdicts
+            .iter()
+            .map(|(verdict, count)| format!("{} {}", style_verdict(verdict), count))
+            .collect::<Vec<_>>();
+        println!("{} {}", header("Verdicts:"), verdicts.join(", "));
+    }
+    if !stats.time_to_ac.is_empty() {
+        println!("{}", header("Time to first AC:"));
+        for (problem, title, secs) in &stats.time_to_ac {
+            println!(
+                "  {:>10}  {:>8}  {}",
+                problem,
+                format_duration(*secs),
+                title
+            );
+        }
+    }
+    if !stats.languages.is_empty() {
+        println!("{}", header("Languages:"));
+        for (lang, count, ac) in &stats.languages {
+            println!("  {:<20} {:>4} submitted, {:>4} AC", lang, count, ac);
+        }
+    }
+}
+
+/// Reports the given lines of a source file with their line numbers.
+pub(crate) fn report_lines(header: &str, lines: &[(usize, &str)]) {
+    if json_output() {
+        let lines = lines
+            .iter()
+            .map(|(lineno, line)| json!({ "line": lineno, "text": line.trim() }))
+            .collect::<Vec<_>>();
+        emit("lines", json!({ "header": header, "lines": lines }));
+        return;
+    }
+    let header = console::style(header.to_string()).yellow();
+    println!("{}", header);
+    for (lineno, line) in lines {
+        println!("{}  {}", style(format!("{:>4}", lineno)).dim(), line.trim());
+    }
+}
+
 fn report_diff(expected: &str, output: &str) {
     let diff = similar::TextDiff::from_lines(expected, output);
     let ops = diff.ops();
@@ -343,3 +812,48 @@ fn report_diff(expected: &str, output: &str) {
         println!("{:4}{}{}{}", left_lineno, left, right_lineno, right);
     }
 }
+
+/// The REPL prompt theme, which masks secrets in the command echoed back after input.
+pub(crate) struct ReplTheme(pub(crate) ColorfulTheme);
+
+impl Theme for ReplTheme {
+    fn format_error(&self, f: &mut dyn std::fmt::Write, err: &str) -> std::fmt::Result {
+        self.0.format_error(f, err)
+    }
+
+    fn format_input_prompt(
+        &self,
+        f: &mut dyn std::fmt::Write,
+        prompt: &str,
+        default: Option<&str>,
+    ) -> std::fmt::Result {
+        self.0.format_input_prompt(f, prompt, default)
+    }
+
+    fn format_input_prompt_selection(
+        &self,
+        f: &mut dyn std::fmt::Write,
+        prompt: &str,
+        sel: &str,
+    ) -> std::fmt::Result {
+        let masked = mask_secrets(sel);
+        self.0
+            .format_input_prompt_selection(f, prompt, masked.as_deref().unwrap_or(sel))
+    }
+}
+
+/// The REPL command history, which leaves out commands carrying secrets.
+pub(crate) struct ReplHistory(pub(crate) BasicHistory);
+
+impl<T: ToString> History<T> for ReplHistory {
+    fn read(&self, pos: usize) -> Option<String> {
+        History::<String>::read(&self.0, pos)
+    }
+
+    fn write(&mut self, val: &T) {
+        let line = val.to_string();
+        if mask_secrets(&line).is_none() {
+            self.0.write(&line);
+        }
+    }
+}
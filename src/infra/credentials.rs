@@ -0,0 +1,154 @@
+use crate::data::{BojConfig, Credentials};
+use crate::infra::console::report_notice;
+use crate::infra::files::{expand_home, write_private};
+use crate::infra::subprocess::{run_output, run_silent};
+use std::path::{Path, PathBuf};
+
+/// Read at startup if it exists and boj.toml does not name another file
+pub(crate) const DEFAULT_CREDENTIALS_FILE: &str = "~/.config/gaboja/credentials.toml";
+
+/// Where the login cookies are stored instead of boj.toml.
+pub(crate) struct CredentialStore {
+    file: PathBuf,
+    /// Prints `<bojautologin> <onlinejudge>`, e.g. from a password manager; takes precedence over the file
+    command: Option<String>,
+}
+
+impl CredentialStore {
+    pub(crate) fn new(config: Option<&BojConfig>) -> Self {
+        let file = config
+            .and_then(|config| config.credentials_file.as_deref())
+            .unwrap_or(DEFAULT_CREDENTIALS_FILE);
+        Self {
+            file: expand_home(file),
+            command: config.and_then(|config| config.credentials_command.clone()),
+        }
+    }
+
+    pub(crate) fn file(&self) -> &Path {
+        &self.file
+    }
+
+    /// True if the credentials come from credentials_command, so that saving them to the file is pointless
+    pub(crate) fn has_command(&self) -> bool {
+        self.command.is_some()
+    }
+
+    /// Reads the credentials from the command or the file. Returns None if neither is available.
+    pub(crate) fn load(&self) -> anyhow::Result<Option<Credentials>> {
+        if let Some(command) = &self.command {
+            let output = run_output(command)
+                .map_err(|err| anyhow::anyhow!("credentials_command failed: {}", err))?;
+            let [bojautologin, onlinejudge] = output.split_whitespace().collect::<Vec<_>>()[..]
+            else {
+                anyhow::bail!(
+                    "credentials_command must print `<bojautologin> <onlinejudge>` separated by whitespace"
+                );
+            };
+            return Ok(Some(Credentials {
+                bojautologin: bojautologin.to_string(),
+                onlinejudge: onlinejudge.to_string(),
+                username: None,
+            }));
+        }
+        if !self.file.exists() {
+            return Ok(None);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&self.file)?.permissions().mode();
+            if mode & 0o077 != 0 {
+                report_notice(&format!(
+                    "Warning: {} can be read by other users; run `chmod 600` on it",
+                    self.file.display()
+                ));
+            }
+        }
+        let content = std::fs::read_to_string(&self.file)?;
+        let credentials = toml::from_str(&content)
+            .map_err(|err| anyhow::anyhow!("{} parse error:\n{}", self.file.display(), err))?;
+        Ok(Some(credentials))
+    }
+
+    /// Writes the credentials to the file, readable only by the current user.
+    pub(crate) fn save(&self, credentials: &Credentials) -> anyhow::Result<()> {
+        write_private(&self.file, &toml::to_string(credentials)?)?;
+        Ok(())
+    }
+}
+
+/// Warns if boj.toml has login cookies in a preset or the start script and is tracked by git, where they can be committed by accident.
+pub(crate) fn warn_tracked_credentials(config: &BojConfig) {
+    let in_presets = config
+        .preset
+        .iter()
+        .any(|preset| preset.credentials.is_some());
+    let in_start = config
+        .start
+        .as_deref()
+        .is_some_and(|start| start.lines().any(|line| mask_secrets(line).is_some()));
+    if !in_presets && !in_start {
+        return;
+    }
+    // Succeeds silently only if the file is tracked
+    if let Ok(None) = run_silent("git ls-files --error-unmatch boj.toml") {
+        report_notice(&format!(
+            "Warning: boj.toml is tracked by git and contains login cookies; move them to {} or use credentials_command",
+            DEFAULT_CREDENTIALS_FILE
+        ));
+    }
+}
+
+/// Replaces the arguments of a command that carries secrets, for echoing it back.
+/// Returns None if the command has nothing to hide.
+pub(crate) fn mask_secrets(line: &str) -> Option<String> {
+    let mut words = line.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some("set"), Some("credentials")) => {
+            let masked = words.map(|_| "********").collect::<Vec<_>>().join(" ");
+            Some(format!("set credentials {}", masked).trim_end().to_string())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask_secrets_hides_cookies() {
+        assert_eq!(
+            mask_secrets("set credentials abc123 def456").as_deref(),
+            Some("set credentials ******** ********")
+        );
+        assert_eq!(
+            mask_secrets("  set   credentials   abc123\tdef456  ").as_deref(),
+            Some("set credentials ******** ********")
+        );
+        let masked = mask_secrets("set credentials 'abc 123' \"def 456\"").unwrap();
+        assert!(!masked.contains("abc") && !masked.contains("456"));
+    }
+
+    #[test]
+    fn mask_secrets_incomplete_command() {
+        assert_eq!(
+            mask_secrets("set credentials").as_deref(),
+            Some("set credentials")
+        );
+        assert_eq!(
+            mask_secrets("set credentials abc123").as_deref(),
+            Some("set credentials ********")
+        );
+    }
+
+    #[test]
+    fn mask_secrets_ignores_other_commands() {
+        assert_eq!(mask_secrets("set lang Rust"), None);
+        assert_eq!(mask_secrets("set"), None);
+        assert_eq!(mask_secrets("setcredentials abc def"), None);
+        assert_eq!(mask_secrets("login"), None);
+        assert_eq!(mask_secrets(""), None);
+    }
+}
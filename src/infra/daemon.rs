@@ -0,0 +1,257 @@
+//! `gaboja daemon`: keeps one browser session and problem cache for all gaboja processes of a project.
+//!
+//! The daemon listens on a Unix socket in the project directory. Each request is a single line of
+//! JSON-RPC 2.0 naming a `Browser` method, with its arguments as a params array; the response holds
+//! the method's return value. Clients open one connection per call.
+
+use crate::data::{BojConfig, Problem, ProblemId};
+use crate::infra::browser::Browser;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Socket of the daemon, relative to the project directory
+pub(crate) const DAEMON_SOCKET: &str = ".gaboja/daemon.sock";
+
+/// Forwards browser calls to the daemon.
+#[derive(Clone)]
+pub(crate) struct DaemonClient {
+    socket: PathBuf,
+}
+
+impl DaemonClient {
+    pub(crate) fn new(socket: &Path) -> Self {
+        Self {
+            socket: socket.to_path_buf(),
+        }
+    }
+
+    /// Calls the method on the daemon's browser and returns its result.
+    pub(crate) fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> anyhow::Result<T> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response = self.request(&request)?;
+        if let Some(error) = response.get("error") {
+            anyhow::bail!(
+                "{}",
+                error["message"].as_str().unwrap_or("Unknown daemon error")
+            );
+        }
+        let result = response.get("result").cloned().unwrap_or(Value::Null);
+        Ok(serde_json::from_value(result)?)
+    }
+
+    #[cfg(unix)]
+    fn request(&self, request: &Value) -> anyhow::Result<Value> {
+        use std::os::unix::net::UnixStream;
+        let mut stream = UnixStream::connect(&self.socket)?;
+        writeln!(stream, "{}", request)?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        if line.is_empty() {
+            anyhow::bail!("The daemon closed the connection");
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    #[cfg(not(unix))]
+    fn request(&self, _request: &Value) -> anyhow::Result<Value> {
+        anyhow::bail!("Unix sockets are not supported on this platform")
+    }
+}
+
+type ProblemCache = Mutex<HashMap<ProblemId, Problem>>;
+
+/// Takes the argument at `index` of the params array.
+fn arg<T: DeserializeOwned>(params: &Value, index: usize) -> anyhow::Result<T> {
+    let value = params.get(index).cloned().unwrap_or(Value::Null);
+    Ok(serde_json::from_value(value)?)
+}
+
+fn dispatch(
+    browser: &Browser,
+    problems: &ProblemCache,
+    method: &str,
+    params: &Value,
+) -> anyhow::Result<Value> {
+    let result = match method {
+        "ping" => Value::Null,
+        "login" => serde_json::to_value(browser.login(&arg(params, 0)?)?)?,
+        "login_with_password" => serde_json::to_value(browser.login_with_password(
+            &arg::<String>(params, 0)?,
+            &arg::<String>(params, 1)?,
+            &arg::<String>(params, 2)?,
+        )?)?,
+        "set_credentials" => serde_json::to_value(browser.set_credentials(&arg(params, 0)?)?)?,
+        "get_username" => serde_json::to_value(browser.get_username()?)?,
+        "get_problem" => {
+            let problem_id: ProblemId = arg(params, 0)?;
+            let cached = problems.lock().unwrap().get(&problem_id).cloned();
+            let problem = match cached {
+                Some(problem) => problem,
+                None => {
+                    let problem = browser.get_problem(&problem_id)?;
+                    problems.lock().unwrap().insert(problem_id, problem.clone());
+                    problem
+                }
+            };
+            serde_json::to_value(problem)?
+        }
+        "submit_solution" => serde_json::to_value(browser.submit_solution(
+            &arg(params, 0)?,
+            &arg::<String>(params, 1)?,
+            &arg::<String>(params, 2)?,
+            arg::<Option<String>>(params, 3)?.as_deref(),
+            arg(params, 4)?,
+        )?)?,
+        "count_submissions" => serde_json::to_value(
+            browser.count_submissions(&arg(params, 0)?, &arg::<String>(params, 1)?)?,
+        )?,
+        "get_submissions" => serde_json::to_value(browser.get_submissions(
+            &arg(params, 0)?,
+            &arg::<String>(params, 1)?,
+            arg(params, 2)?,
+        )?)?,
+        "get_source" => serde_json::to_value(browser.get_source(&arg::<String>(params, 0)?)?)?,
+        "get_languages" => serde_json::to_value(browser.get_languages(&arg(params, 0)?)?)?,
+        "get_submission_status" => {
+            serde_json::to_value(browser.get_submission_status(&arg::<String>(params, 0)?)?)?
+        }
+        "get_submission_status_json" => {
+            serde_json::to_value(browser.get_submission_status_json(&arg::<String>(params, 0)?)?)?
+        }
+        "screenshot" => serde_json::to_value(browser.screenshot()?)?,
+        "source" => serde_json::to_value(browser.source()?)?,
+        _ => anyhow::bail!("Unknown method `{}`", method),
+    };
+    Ok(result)
+}
+
+/// Quits the browser and removes the socket before the daemon exits.
+fn stop(browser: &Browser, socket: &Path) -> ! {
+    std::fs::remove_file(socket).ok();
+    let code = match browser.clone().quit() {
+        Ok(()) => 0,
+        Err(err) => {
+            println!("daemon: Failed to quit the browser: {}", err);
+            1
+        }
+    };
+    println!("Daemon stopped");
+    std::process::exit(code)
+}
+
+#[cfg(unix)]
+fn serve_connection(
+    stream: std::os::unix::net::UnixStream,
+    browser: &Browser,
+    problems: &ProblemCache,
+    socket: &Path,
+) -> anyhow::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = serde_json::from_str::<Value>(&line).unwrap_or(Value::Null);
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = request.get("method").and_then(Value::as_str).unwrap_or("");
+        let params = request.get("params").cloned().unwrap_or(json!([]));
+        if method == "shutdown" {
+            writeln!(
+                writer,
+                "{}",
+                json!({ "jsonrpc": "2.0", "id": id, "result": null })
+            )?;
+            stop(browser, socket);
+        }
+        let response = match dispatch(browser, problems, method, &params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32000, "message": err.to_string() },
+            }),
+        };
+        writeln!(writer, "{}", response)?;
+    }
+    Ok(())
+}
+
+/// Runs the daemon in the foreground until it is stopped by `gaboja daemon stop` or Ctrl+C.
+/// `args` is empty to start the daemon, or `stop` to stop the running one.
+#[cfg(unix)]
+pub(crate) fn run(args: &[String]) -> anyhow::Result<()> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+    let socket = Path::new(DAEMON_SOCKET);
+    match args {
+        [] => {}
+        [cmd] if cmd == "stop" => {
+            DaemonClient::new(socket)
+                .call::<()>("shutdown", json!([]))
+                .map_err(|err| anyhow::anyhow!("No daemon is running: {}", err))?;
+            println!("Daemon stopped");
+            return Ok(());
+        }
+        _ => anyhow::bail!("Usage: gaboja daemon [stop]"),
+    }
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            anyhow::bail!("A daemon is already running on {}", DAEMON_SOCKET);
+        }
+        // left by a daemon that did not exit cleanly
+        std::fs::remove_file(socket)?;
+    }
+    if let Some(dir) = socket.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    // no boj.toml means the defaults, but a broken one should not be silently ignored
+    let config = match BojConfig::from_config() {
+        Ok(config) => config.webdriver,
+        Err(err)
+            if err
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|err| err.kind() == std::io::ErrorKind::NotFound) =>
+        {
+            Default::default()
+        }
+        // the parse error is already printed
+        Err(err) if err.is::<toml::de::Error>() => {
+            anyhow::bail!("daemon: Not starting with a broken boj.toml")
+        }
+        Err(err) => anyhow::bail!("daemon: Failed to read boj.toml: {}", err),
+    };
+    let browser = Browser::new(&config)?;
+    let listener = UnixListener::bind(socket)?;
+    let problems: Arc<ProblemCache> = Arc::new(Mutex::new(HashMap::new()));
+    {
+        let browser = browser.clone();
+        ctrlc::set_handler(move || stop(&browser, Path::new(DAEMON_SOCKET)))?;
+    }
+    println!("Daemon listening on {}", DAEMON_SOCKET);
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let browser = browser.clone();
+        let problems = problems.clone();
+        std::thread::spawn(move || {
+            // the client notices a broken connection by itself
+            serve_connection(stream, &browser, &problems, Path::new(DAEMON_SOCKET)).ok();
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn run(_args: &[String]) -> anyhow::Result<()> {
+    anyhow::bail!("The daemon is not supported on this platform")
+}
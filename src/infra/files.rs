@@ -0,0 +1,158 @@
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// Expands a path pattern into the list of existing files it matches.
+/// `*` and `?` match within a single path component, and `**` matches any number of directories.
+/// A pattern without wildcards is returned as is, whether the file exists or not.
+pub(crate) fn expand_glob(pattern: &str) -> Vec<PathBuf> {
+    if !pattern.contains(['*', '?']) {
+        return vec![PathBuf::from(pattern)];
+    }
+    fn component_regex(component: &str) -> Regex {
+        let escaped = regex::escape(component)
+            .replace(r"\*", "[^/]*")
+            .replace(r"\?", "[^/]");
+        Regex::new(&format!("^{}$", escaped)).unwrap()
+    }
+    fn walk(base: &Path, components: &[&str], out: &mut Vec<PathBuf>) {
+        let Some((&first, rest)) = components.split_first() else {
+            if base.is_file() {
+                out.push(base.to_path_buf());
+            }
+            return;
+        };
+        if first == "**" {
+            walk(base, rest, out);
+            let Ok(entries) = std::fs::read_dir(base) else {
+                return;
+            };
+            for entry in entries.flatten() {
+                // symlinked directories are not followed, as they can form a cycle
+                if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                    walk(&entry.path(), components, out);
+                }
+            }
+        } else if first.contains(['*', '?']) {
+            let re = component_regex(first);
+            let Ok(entries) = std::fs::read_dir(base) else {
+                return;
+            };
+            for entry in entries.flatten() {
+                if re.is_match(&entry.file_name().to_string_lossy()) {
+                    walk(&entry.path(), rest, out);
+                }
+            }
+        } else {
+            walk(&base.join(first), rest, out);
+        }
+    }
+    let (base, pattern) = match pattern.strip_prefix('/') {
+        Some(rest) => (Path::new("/"), rest),
+        None => (Path::new("."), pattern),
+    };
+    let components = pattern
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>();
+    let mut out = vec![];
+    walk(base, &components, &mut out);
+    for path in &mut out {
+        if let Ok(relative) = path.strip_prefix(".") {
+            *path = relative.to_path_buf();
+        }
+    }
+    out.sort();
+    out.dedup();
+    out
+}
+
+/// Checks if the path matches the pattern, where `*` and `?` match within a single path component
+/// and `**/` matches any number of directories.
+/// A pattern without `/` is matched against the file name only.
+pub(crate) fn matches_glob(pattern: &str, path: &Path) -> bool {
+    let target = if pattern.contains('/') {
+        path.to_string_lossy().replace('\\', "/")
+    } else if let Some(file_name) = path.file_name() {
+        file_name.to_string_lossy().to_string()
+    } else {
+        return false;
+    };
+    let re = regex::escape(pattern)
+        .replace(r"\*\*/", "(.*/)?")
+        .replace(r"\*", "[^/]*")
+        .replace(r"\?", "[^/]");
+    Regex::new(&format!("^{}$", re)).is_ok_and(|re| re.is_match(&target))
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher`, the result does not change between Rust releases,
+/// so it can be stored in files.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// Writes the length before the bytes, so that adjacent fields cannot run into each other.
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+}
+
+/// Hashes the paths and contents of the given files. Missing files are hashed as absent.
+pub(crate) fn hash_files(paths: &[PathBuf]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    for path in paths {
+        hasher.write_field(path.to_string_lossy().as_bytes());
+        match std::fs::read(path) {
+            Ok(content) => {
+                hasher.write(&[1]);
+                hasher.write_field(&content);
+            }
+            Err(_) => hasher.write(&[0]),
+        }
+    }
+    hasher.0
+}
+
+/// Hashes a source string, formatted as 16 hex digits.
+pub(crate) fn hash_source(source: &str) -> String {
+    let mut hasher = Fnv1a::new();
+    hasher.write(source.as_bytes());
+    format!("{:016x}", hasher.0)
+}
+
+/// Expands a leading `~/` to the home directory, for files kept in the user config dir.
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    match (path.strip_prefix("~/"), home) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Writes a file that only the current user can read, for cookies and credentials.
+/// Missing parent directories are created.
+pub(crate) fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    // the mode is applied only when the file is created
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(content.as_bytes())
+}
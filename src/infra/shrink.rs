@@ -0,0 +1,221 @@
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    /// `//` and `/* */` comments, `"` strings and `'` chars (C, C++, Rust, Java, ...)
+    CLike,
+    /// `#` comments, `'`/`"` strings and triple-quoted strings
+    Python,
+}
+
+fn syntax_of(file: &Path) -> Option<Syntax> {
+    let ext = file.extension()?.to_string_lossy().to_ascii_lowercase();
+    match &ext[..] {
+        "c" | "cc" | "cpp" | "cxx" | "c++" | "h" | "hpp" | "rs" | "java" | "kt" | "go" | "cs"
+        | "js" | "ts" | "swift" | "scala" | "d" => Some(Syntax::CLike),
+        "py" => Some(Syntax::Python),
+        _ => None,
+    }
+}
+
+/// Removes comments outside string literals. Newlines are kept so that line numbers do not change.
+/// Returns None if the comment syntax of the file is unknown.
+pub(crate) fn strip_comments(file: &Path, source: &str) -> Option<String> {
+    let syntax = syntax_of(file)?;
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut out = String::with_capacity(source.len());
+    let mut i = 0;
+    let starts_with = |i: usize, s: &str| {
+        s.chars()
+            .enumerate()
+            .all(|(j, c)| chars.get(i + j) == Some(&c))
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        if syntax == Syntax::CLike && starts_with(i, "//") || syntax == Syntax::Python && c == '#' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if syntax == Syntax::CLike && starts_with(i, "/*") {
+            i += 2;
+            while i < chars.len() && !starts_with(i, "*/") {
+                if chars[i] == '\n' {
+                    out.push('\n');
+                }
+                i += 1;
+            }
+            i += 2;
+            // keep tokens on both sides apart
+            out.push(' ');
+            continue;
+        }
+        if syntax == Syntax::CLike && c == 'r' && raw_string_prefix(&chars[..i]) {
+            // Rust raw string r"..." or r#"..."#, which has no escapes
+            let hashes = chars[i + 1..].iter().take_while(|&&c| c == '#').count();
+            if chars.get(i + 1 + hashes) == Some(&'"') {
+                let closing = format!("\"{}", "#".repeat(hashes));
+                let start = i;
+                i += 2 + hashes;
+                while i < chars.len() && !starts_with(i, &closing) {
+                    i += 1;
+                }
+                i = (i + closing.len()).min(chars.len());
+                out.extend(&chars[start..i]);
+                continue;
+            }
+        }
+        let quote = match c {
+            '"' => Some("\""),
+            '\'' if syntax == Syntax::Python => Some("'"),
+            // a char literal is at most a few chars long; anything else is a Rust lifetime
+            '\'' if chars[i + 1..]
+                .iter()
+                .take(4)
+                .position(|&c| c == '\'')
+                .is_some() =>
+            {
+                Some("'")
+            }
+            _ => None,
+        };
+        let Some(quote) = quote else {
+            out.push(c);
+            i += 1;
+            continue;
+        };
+        let quote = if syntax == Syntax::Python && starts_with(i, &quote.repeat(3)) {
+            quote.repeat(3)
+        } else {
+            quote.to_string()
+        };
+        // copy the whole literal as is
+        for c in quote.chars() {
+            out.push(c);
+            i += 1;
+        }
+        while i < chars.len() && !starts_with(i, &quote) {
+            if chars[i] == '\\' && i + 1 < chars.len() {
+                out.push(chars[i]);
+                i += 1;
+            }
+            out.push(chars[i]);
+            i += 1;
+        }
+        for c in quote.chars() {
+            if i < chars.len() {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    Some(out)
+}
+
+/// Checks if an `r` after `before` starts a raw string rather than ending an identifier,
+/// allowing the `b` of a raw byte string.
+fn raw_string_prefix(before: &[char]) -> bool {
+    let is_ident = |c: &char| c.is_alphanumeric() || *c == '_';
+    match before {
+        [.., prev, 'b'] => !is_ident(prev),
+        ['b'] => true,
+        [.., prev] => !is_ident(prev),
+        [] => true,
+    }
+}
+
+/// Removes trailing whitespace and blank lines.
+/// Indentation is also removed for C-like syntaxes, and kept for others where it may be significant.
+pub(crate) fn minify_whitespace(file: &Path, source: &str) -> String {
+    let keep_indent = syntax_of(file) != Some(Syntax::CLike);
+    source
+        .lines()
+        .map(|line| {
+            if keep_indent {
+                line.trim_end()
+            } else {
+                line.trim()
+            }
+        })
+        .filter(|line| !line.is_empty())
+        .flat_map(|line| [line, "\n"])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(file: &str, source: &str) -> Option<String> {
+        strip_comments(Path::new(file), source)
+    }
+
+    #[test]
+    fn strip_comments_c_like() {
+        let source = "int a; // one\n/* two\nthree */int b;\n";
+        assert_eq!(strip("a.cpp", source).unwrap(), "int a; \n\n int b;\n");
+    }
+
+    #[test]
+    fn strip_comments_keeps_literals() {
+        let source = r#"s = "// \" /*"; c = '/'; // x"#;
+        assert_eq!(
+            strip("a.c", source).unwrap(),
+            r#"s = "// \" /*"; c = '/'; "#
+        );
+    }
+
+    #[test]
+    fn strip_comments_rust_lifetimes() {
+        let source = "fn f<'a>(x: &'a str) -> &'a str { x } // y\n";
+        assert_eq!(
+            strip("a.rs", source).unwrap(),
+            "fn f<'a>(x: &'a str) -> &'a str { x } \n"
+        );
+    }
+
+    #[test]
+    fn strip_comments_rust_raw_strings() {
+        let source = r####"let a = r#"// "not" /* end"#; // x
+let b = br"\"; // y
+let c = r; // z
+"####;
+        assert_eq!(
+            strip("a.rs", source).unwrap(),
+            "let a = r#\"// \"not\" /* end\"#; \nlet b = br\"\\\"; \nlet c = r; \n"
+        );
+    }
+
+    #[test]
+    fn strip_comments_python() {
+        let source = "s = '''# not\n''' # x\nt = \"#\"  # y\n";
+        assert_eq!(
+            strip("a.py", source).unwrap(),
+            "s = '''# not\n''' \nt = \"#\"  \n"
+        );
+    }
+
+    #[test]
+    fn strip_comments_unknown_syntax() {
+        assert_eq!(strip("a.hs", "-- x\n"), None);
+    }
+
+    #[test]
+    fn minify_whitespace_c_like() {
+        let source = "int main() {  \n\n    return 0;\n}\n";
+        assert_eq!(
+            minify_whitespace(Path::new("a.cpp"), source),
+            "int main() {\nreturn 0;\n}\n"
+        );
+    }
+
+    #[test]
+    fn minify_whitespace_keeps_indent_unless_c_like() {
+        let source = "def f():  \n\n    return 0\n";
+        let minified = "def f():\n    return 0\n";
+        assert_eq!(minify_whitespace(Path::new("a.py"), source), minified);
+        assert_eq!(minify_whitespace(Path::new("a.hs"), source), minified);
+        assert_eq!(minify_whitespace(Path::new("Makefile"), source), minified);
+    }
+}
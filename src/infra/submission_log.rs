@@ -0,0 +1,32 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Appends a record to the given log in JSON Lines format, creating the file if needed.
+pub(crate) fn append_record<T: Serialize>(path: &Path, record: &T) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Reads all records from the given log. Lines that fail to parse are skipped.
+/// A missing log is treated as empty.
+pub(crate) fn read_records<T: DeserializeOwned>(path: &Path) -> anyhow::Result<Vec<T>> {
+    let Ok(file) = std::fs::File::open(path) else {
+        return Ok(vec![]);
+    };
+    let mut records = vec![];
+    for line in BufReader::new(file).lines() {
+        if let Ok(record) = serde_json::from_str(&line?) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
@@ -1,4 +1,5 @@
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex, Once, Weak};
 use std::time::{Duration, Instant};
 use tokio::io::AsyncWriteExt;
 use tokio::runtime;
@@ -15,21 +16,117 @@ fn spawn_cmd(cmd: &str) -> Command {
     }
 }
 
-/// Background-running processes do not receive Ctrl+C signal from the parent.
-/// This is necessary to keep geckodriver alive.
-/// On Linux, this is achieved with `<command> &`.
-/// On Windows, CMD's START built-in has `/B` option. https://superuser.com/a/591084 This feature is not tested yet.
-pub(crate) fn spawn_cmd_background(cmd: &str) -> Command {
-    if cfg!(target_os = "windows") {
-        let mut command = Command::new("cmd");
-        let cmd = format!("START /B {}", cmd);
-        command.arg("/C").arg(&cmd);
-        command
-    } else {
-        let mut command = Command::new("sh");
-        let cmd = format!("{}&", cmd);
-        command.arg("-c").arg(&cmd);
-        command
+/// A long-running helper process, such as the WebDriver server, that must not outlive gaboja.
+/// It is killed on `kill`, when dropped, and when gaboja panics.
+/// On Linux it is also terminated when gaboja dies by a signal.
+pub(crate) struct OwnedChild {
+    child: Mutex<Child>,
+}
+
+/// How long the process group gets to exit after SIGTERM before it is killed
+#[cfg(unix)]
+const KILL_GRACE: Duration = Duration::from_secs(2);
+
+impl OwnedChild {
+    /// Kills the process if it is still running. Calling this again does nothing.
+    /// On Unix, the whole process group, which includes the browser started by the WebDriver server,
+    /// is asked to terminate first and killed if it does not exit in time.
+    #[cfg(unix)]
+    pub(crate) fn kill(&self) {
+        let mut child = self.child.lock().unwrap_or_else(|err| err.into_inner());
+        if !matches!(child.try_wait(), Ok(None)) {
+            return;
+        }
+        // the child leads its own process group, see `spawn_owned`
+        let group = child.id() as libc::pid_t;
+        // SAFETY: killpg only sends a signal
+        unsafe { libc::killpg(group, libc::SIGTERM) };
+        let deadline = Instant::now() + KILL_GRACE;
+        while Instant::now() < deadline {
+            let exited = !matches!(child.try_wait(), Ok(None));
+            // SAFETY: signal 0 only checks whether any process of the group is left
+            if exited && unsafe { libc::killpg(group, 0) } == -1 {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        // SAFETY: as above
+        unsafe { libc::killpg(group, libc::SIGKILL) };
+        child.wait().ok();
+    }
+
+    /// Kills the process if it is still running. Calling this again does nothing.
+    #[cfg(not(unix))]
+    pub(crate) fn kill(&self) {
+        let mut child = self.child.lock().unwrap_or_else(|err| err.into_inner());
+        if let Ok(None) = child.try_wait() {
+            child.kill().ok();
+            child.wait().ok();
+        }
+    }
+}
+
+impl Drop for OwnedChild {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Children that are killed if gaboja panics
+static OWNED_CHILDREN: Mutex<Vec<Weak<OwnedChild>>> = Mutex::new(vec![]);
+
+/// Starts the program with the arguments as an `OwnedChild`.
+/// The child does not receive Ctrl+C from the terminal, which gaboja handles by itself.
+pub(crate) fn spawn_owned(program: &str, args: &[String]) -> anyhow::Result<Arc<OwnedChild>> {
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+        #[cfg(target_os = "linux")]
+        // SAFETY: prctl is async-signal-safe and only changes the state of the new process
+        unsafe {
+            command.pre_exec(|| {
+                if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+    let child = Arc::new(OwnedChild {
+        child: Mutex::new(command.spawn()?),
+    });
+
+    static PANIC_HOOK: Once = Once::new();
+    PANIC_HOOK.call_once(|| {
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            kill_owned_children();
+            default_hook(info);
+        }));
+    });
+    let mut children = OWNED_CHILDREN.lock().unwrap_or_else(|err| err.into_inner());
+    children.retain(|child| child.strong_count() > 0);
+    children.push(Arc::downgrade(&child));
+    Ok(child)
+}
+
+fn kill_owned_children() {
+    let children = OWNED_CHILDREN.lock().unwrap_or_else(|err| err.into_inner());
+    for child in children.iter().filter_map(Weak::upgrade) {
+        child.kill();
     }
 }
 
@@ -61,6 +158,20 @@ pub(crate) fn run_silent(cmd: &str) -> anyhow::Result<Option<String>> {
     Ok(stderr)
 }
 
+/// Runs the given command and returns its STDOUT. STDIN and STDERR are left to the user,
+/// e.g. for a password manager asking for the passphrase.
+pub(crate) fn run_output(cmd: &str) -> anyhow::Result<String> {
+    let output = spawn_cmd(cmd)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!("`{}` exited with {}", cmd, output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
 /// Runs the given command and lets the user interact with it.
 pub(crate) fn run_interactive(cmd: &str) -> anyhow::Result<()> {
     let mut child = spawn_cmd(cmd)
@@ -118,3 +229,52 @@ pub(crate) fn run_with_input_timed(
         }))
     })
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    /// True if a process of the child's group is still running. Zombies do not count,
+    /// as orphans are reaped by init whenever it gets to them.
+    fn group_alive(child: &OwnedChild) -> bool {
+        let group = child.child.lock().unwrap().id().to_string();
+        std::fs::read_dir("/proc").unwrap().flatten().any(|entry| {
+            let stat = std::fs::read_to_string(entry.path().join("stat")).unwrap_or_default();
+            // pid (comm) state ppid pgrp ...
+            let fields = stat
+                .rsplit_once(')')
+                .map(|(_, rest)| rest.split_whitespace().collect::<Vec<_>>())
+                .unwrap_or_default();
+            fields.len() > 2 && fields[2] == group && fields[0] != "Z"
+        })
+    }
+
+    #[test]
+    fn kill_terminates_the_process_group() {
+        let args = ["-c".to_string(), "sleep 30 & wait".to_string()];
+        let child = spawn_owned("sh", &args).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(group_alive(&child));
+        let start = Instant::now();
+        child.kill();
+        assert!(!group_alive(&child));
+        assert!(start.elapsed() < KILL_GRACE);
+    }
+
+    #[test]
+    fn kill_forces_a_group_ignoring_sigterm() {
+        let args = [
+            "-c".to_string(),
+            "trap '' TERM; sleep 30 & wait".to_string(),
+        ];
+        let child = spawn_owned("sh", &args).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        child.kill();
+        // SIGKILL is delivered asynchronously
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while group_alive(&child) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!group_alive(&child));
+    }
+}
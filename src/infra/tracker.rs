@@ -0,0 +1,242 @@
+use crate::data::{is_judging, verdict_from_class, SubmissionRecord, SubmissionStatus};
+use crate::infra::browser::Browser;
+use crate::infra::console::{
+    json_output, report_notice, report_submission_progress, report_submission_result,
+    report_tracking_stopped,
+};
+use crate::infra::submission_log::append_record;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Poll interval while waiting in the queue or compiling, which usually ends soon
+const FAST_POLL: Duration = Duration::from_millis(500);
+/// Poll interval at the start of judging, growing by half each poll up to MAX_POLL
+const JUDGING_POLL: Duration = Duration::from_secs(1);
+const MAX_POLL: Duration = Duration::from_secs(5);
+/// Tracking stops with the last known status if judging takes longer than this
+const GIVE_UP_AFTER: Duration = Duration::from_secs(10 * 60);
+/// Tracking stops after this many failed polls in a row
+const MAX_FAILURES: usize = 10;
+
+/// A submission whose status is being watched.
+#[derive(Clone)]
+pub(crate) struct TrackedSubmission {
+    /// Status text and verdict are updated as the judging goes on
+    pub(crate) record: SubmissionRecord,
+    pub(crate) status_class: String,
+    pub(crate) finished: bool,
+    /// Why tracking stopped before the verdict, if it did
+    pub(crate) stopped: Option<String>,
+    /// If set, the result is shown by whoever is watching instead of being reported on finish
+    pub(crate) watched: bool,
+    /// Submission log the final record is appended to
+    log: PathBuf,
+}
+
+/// Output of the background threads, held until it can be printed without breaking the prompt
+enum Report {
+    Result(SubmissionRecord),
+    Stopped(SubmissionRecord, String),
+    LogFailed(String),
+}
+
+/// Polls the status of submissions in background threads so that the REPL stays usable while judging.
+pub(crate) struct Tracker {
+    browser: Browser,
+    submissions: Arc<Mutex<Vec<TrackedSubmission>>>,
+    reports: Arc<Mutex<Vec<Report>>>,
+}
+
+impl Tracker {
+    pub(crate) fn new(browser: Browser) -> Self {
+        Self {
+            browser,
+            submissions: Arc::new(Mutex::new(vec![])),
+            reports: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Starts tracking the submission. The final record is appended to `log` once judging is done.
+    /// Returns the index to look up the submission with.
+    pub(crate) fn track(&self, record: SubmissionRecord, log: PathBuf, watched: bool) -> usize {
+        let submission_id = record.submission_id.clone();
+        let index = {
+            let mut submissions = self.submissions.lock().unwrap();
+            submissions.push(TrackedSubmission {
+                record,
+                // judging state until the first poll
+                status_class: "result-wait".to_string(),
+                finished: false,
+                stopped: None,
+                watched,
+                log,
+            });
+            submissions.len() - 1
+        };
+        let browser = self.browser.clone();
+        let submissions = self.submissions.clone();
+        let reports = self.reports.clone();
+        thread::spawn(move || {
+            let start = Instant::now();
+            let mut interval = FAST_POLL;
+            let mut failures = 0;
+            loop {
+                let status = match &submission_id {
+                    Some(id) => poll(&browser, id),
+                    None => Err(anyhow::anyhow!("Submission id unknown")),
+                };
+                let mut submissions = submissions.lock().unwrap();
+                let submission = &mut submissions[index];
+                // logged as pending on exit
+                if submission.finished {
+                    break;
+                }
+                match status {
+                    Ok(status) => {
+                        failures = 0;
+                        submission.record.time = status.time;
+                        submission.record.memory = status.memory;
+                        let judging = is_judging(&status.text, &status.class);
+                        let changed = submission.record.status != status.text;
+                        interval = if WAITING.is_match(&status.class) {
+                            FAST_POLL
+                        } else {
+                            (interval * 3 / 2).clamp(JUDGING_POLL, MAX_POLL)
+                        };
+                        submission.record.status = status.text;
+                        submission.status_class = status.class;
+                        if !judging {
+                            break finish(submission, &reports, None);
+                        }
+                        if changed {
+                            report_submission_progress(&submission.record);
+                        }
+                    }
+                    Err(err) => {
+                        failures += 1;
+                        interval = (interval * 2).min(MAX_POLL);
+                        if failures >= MAX_FAILURES || submission_id.is_none() {
+                            let reason = format!("Failed to get the status: {}", err);
+                            break finish(submission, &reports, Some(reason));
+                        }
+                    }
+                }
+                if start.elapsed() >= GIVE_UP_AFTER {
+                    let reason = format!(
+                        "Judging took more than {} minutes",
+                        GIVE_UP_AFTER.as_secs() / 60
+                    );
+                    break finish(submission, &reports, Some(reason));
+                }
+                drop(submissions);
+                thread::sleep(interval);
+            }
+        });
+        index
+    }
+
+    pub(crate) fn get(&self, index: usize) -> TrackedSubmission {
+        self.submissions.lock().unwrap()[index].clone()
+    }
+
+    /// Lets the tracker report the result of the submission by itself.
+    pub(crate) fn unwatch(&self, index: usize) {
+        self.submissions.lock().unwrap()[index].watched = false;
+    }
+
+    /// Returns all tracked submissions, oldest first.
+    pub(crate) fn submissions(&self) -> Vec<TrackedSubmission> {
+        self.submissions.lock().unwrap().clone()
+    }
+
+    /// Prints the results that arrived in the background since the last call.
+    /// Called before the prompt is shown, as printing during the prompt would break the input line.
+    pub(crate) fn print_reports(&self) {
+        let reports = std::mem::take(&mut *self.reports.lock().unwrap());
+        for report in reports {
+            match report {
+                Report::Result(record) => report_submission_result(&record),
+                Report::Stopped(record, reason) => report_tracking_stopped(&record, &reason),
+                Report::LogFailed(err) => report_notice(&err),
+            }
+        }
+    }
+
+    /// Appends the submissions still being judged to the submission log with a `pending` verdict,
+    /// so that they are not lost on exit. Returns how many there were.
+    pub(crate) fn log_pending(&self) -> usize {
+        let mut submissions = self.submissions.lock().unwrap();
+        let mut count = 0;
+        for submission in submissions.iter_mut().filter(|s| !s.finished) {
+            submission.finished = true;
+            submission.record.verdict = Some("pending".to_string());
+            if let Err(err) = append_record(&submission.log, &submission.record) {
+                report_notice(&format!(
+                    "submit: Failed to write the submission log: {}",
+                    err
+                ));
+            }
+            count += 1;
+        }
+        count
+    }
+}
+
+static WAITING: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"result-wait|result-rejudge-wait|result-compile").unwrap());
+
+/// Gets the status from the JSON status channel, falling back to the status page whenever the channel fails.
+fn poll(browser: &Browser, submission_id: &str) -> anyhow::Result<SubmissionStatus> {
+    match browser.get_submission_status_json(submission_id) {
+        Ok(Some(status)) => Ok(status),
+        Ok(None) | Err(_) => browser.get_submission_status(submission_id),
+    }
+}
+
+/// Fills in the verdict and score, writes the record to the submission log and reports it if nobody is watching.
+/// If tracking stopped before the verdict, the last known status is logged and reported along with the reason.
+/// In JSON mode the events are sent right away; otherwise they wait for `print_reports`.
+fn finish(
+    submission: &mut TrackedSubmission,
+    reports: &Mutex<Vec<Report>>,
+    stopped: Option<String>,
+) {
+    static SCORE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(\d+(?:\.\d+)?)\s*(?:점|points?)").unwrap());
+    let record = &mut submission.record;
+    record.verdict = verdict_from_class(&submission.status_class);
+    record.score = SCORE
+        .captures(&record.status)
+        .and_then(|caps| caps[1].parse().ok());
+    submission.finished = true;
+    let mut queued = vec![];
+    if let Err(err) = append_record(&submission.log, &*record) {
+        queued.push(Report::LogFailed(format!(
+            "submit: Failed to write the submission log: {}",
+            err
+        )));
+    }
+    // in JSON mode the watcher draws nothing, so the result is always reported here
+    if !submission.watched || json_output() {
+        queued.push(match &stopped {
+            Some(reason) => Report::Stopped(record.clone(), reason.clone()),
+            None => Report::Result(record.clone()),
+        });
+    }
+    submission.stopped = stopped;
+    if json_output() {
+        for report in queued {
+            match report {
+                Report::Result(record) => report_submission_result(&record),
+                Report::Stopped(record, reason) => report_tracking_stopped(&record, &reason),
+                Report::LogFailed(err) => report_notice(&err),
+            }
+        }
+    } else {
+        reports.lock().unwrap().extend(queued);
+    }
+}
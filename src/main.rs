@@ -1,12 +1,15 @@
 use dialoguer::{theme::ColorfulTheme, BasicHistory, Input};
 
-use crate::command::InputCommand;
+use crate::command::{quote_arg, split_commands, InputCommand};
+use crate::data::ProblemId;
 use crate::global_state::GlobalState;
+use crate::infra::console::{report_error, set_json_output, ReplHistory, ReplTheme};
 
 mod command;
 mod data;
 mod global_state;
 mod infra;
+mod server;
 
 // const LANGUAGE: &str = "Rust 2021";
 // const SOURCE: &str = r#"fn main() { println!("Hello World!"); }"#;
@@ -33,14 +36,110 @@ mod infra;
 // inputs that don't start with a known keyword are assumed to be shell command
 // without setting, lang = 'Rust 2021', runcmd = 'cargo run --release', input = 'input.txt'
 
+/// Commands that take the problem as the first argument on the command line, e.g. `gaboja test 1000`
+const PROBLEM_FIRST: &[&str] = &["run", "test", "watch", "submit", "history"];
+
+/// Exit code when a test, build or submission did not pass
+const EXIT_FAILED: i32 = 1;
+/// Exit code when a command could not be parsed or executed
+const EXIT_ERROR: i32 = 2;
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// Turns the command line arguments into lines of commands.
+/// `-c 'prob 1000; test'` runs a script, and anything else is a single command.
+fn batch_commands(args: &[String]) -> Result<Vec<String>, String> {
+    if args[0] == "-c" {
+        let [_, script] = args else {
+            return Err("Usage: gaboja -c '<command>; <command>; ...'".to_string());
+        };
+        return Ok(split_commands(script));
+    }
+    let mut commands = vec![];
+    let mut args = args;
+    if args.len() >= 2
+        && PROBLEM_FIRST.contains(&&args[0][..])
+        && args[1].parse::<ProblemId>().is_ok()
+    {
+        commands.push(format!("prob {}", args[1]));
+        commands.push(args[0].clone());
+        args = &args[2..];
+    } else {
+        commands.push(args[0].clone());
+        args = &args[1..];
+    }
+    let command = commands.last_mut().unwrap();
+    for arg in args {
+        command.push(' ');
+        command.push_str(&quote_arg(arg));
+    }
+    Ok(commands)
+}
+
+/// Runs the commands one by one, stopping at the first error or failure, and returns the exit code.
+fn run_batch(state: &mut GlobalState, commands: &[String]) -> i32 {
+    for line in commands {
+        let cmd = match line.parse::<InputCommand>() {
+            Ok(cmd) => cmd,
+            Err(err) => {
+                report_error(&err);
+                return EXIT_ERROR;
+            }
+        };
+        if cmd.is_exit() {
+            break;
+        }
+        if let Err(err) = state.execute(&cmd) {
+            report_error(&err);
+            return EXIT_ERROR;
+        }
+        if state.ctrlc_channel.try_recv().is_ok() {
+            return EXIT_INTERRUPTED;
+        }
+        if state.failed {
+            return EXIT_FAILED;
+        }
+    }
+    0
+}
+
 fn main() -> anyhow::Result<()> {
-    let mut history = BasicHistory::new().max_entries(8).no_duplicates(true);
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().is_some_and(|arg| arg == "--json") {
+        args.remove(0);
+        set_json_output(true);
+    }
+    if args.first().is_some_and(|arg| arg == "serve") {
+        return server::run(&args[1..]);
+    }
+    if args.first().is_some_and(|arg| arg == "daemon") {
+        return infra::daemon::run(&args[1..]);
+    }
+    if !args.is_empty() {
+        let commands = match batch_commands(&args) {
+            Ok(commands) => commands,
+            Err(usage) => {
+                println!("{}", usage);
+                std::process::exit(EXIT_ERROR);
+            }
+        };
+        // The browser is started only if a command needs it
+        let mut state = GlobalState::new(true)?;
+        let code = run_batch(&mut state, &commands);
+        state.quit()?;
+        std::process::exit(code);
+    }
+
+    let mut history = ReplHistory(BasicHistory::new().max_entries(8).no_duplicates(true));
 
     // Reading boj.toml is done inside GlobalState::new
-    let mut state = GlobalState::new()?;
+    let mut state = GlobalState::new(false)?;
+    state.browser()?;
 
     loop {
-        let input = Input::<InputCommand>::with_theme(&ColorfulTheme::default())
+        if let Some(tracker) = state.tracker.get() {
+            tracker.print_reports();
+        }
+        let input = Input::<InputCommand>::with_theme(&ReplTheme(ColorfulTheme::default()))
             .with_prompt("BOJ")
             .history_with(&mut history)
             .interact_text();
@@ -51,7 +150,7 @@ fn main() -> anyhow::Result<()> {
                     break;
                 }
                 if let Err(e) = state.execute(&cmd) {
-                    println!("Error: {}", e);
+                    report_error(&e);
                 }
                 if state.ctrlc_channel.try_recv().is_ok() {
                     // consume the ctrlc queue
@@ -0,0 +1,253 @@
+//! JSON-RPC 2.0 server for editor integration.
+//!
+//! Messages are single lines of JSON in both directions. A request's method is a command name
+//! (`prob`, `build`, `run`, `test`, `submit`, `set`, ...) with its arguments as params:
+//! an array holds positional arguments, and an object holds keyword arguments
+//! plus positional ones under `args`. The `execute` method takes a whole command line as
+//! `{"command": "..."}`, and `shutdown` stops the server.
+//!
+//! While a request runs, the JSON output events are sent as `event` notifications,
+//! except the tables of `status`, `history` and `stats`, which are returned in the result.
+//! Over stdio, anything else written to stdout (e.g. by programs run with `$`) goes to stderr.
+
+use crate::command::{quote_arg, InputCommand};
+use crate::global_state::GlobalState;
+use crate::infra::console::{set_event_sink, set_json_output};
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const INVALID_PARAMS: i64 = -32602;
+/// The command was understood but failed
+const EXECUTE_ERROR: i64 = -32000;
+
+type Writer = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// Events that hold the output of a command rather than its progress, returned in the result
+const RESULT_EVENTS: [&str; 3] = ["submissions", "history", "stats"];
+
+fn send(writer: &Writer, message: Value) {
+    let mut writer = writer.lock().unwrap();
+    // a client that went away is noticed when reading the next request
+    writeln!(writer, "{}", message).ok();
+    writer.flush().ok();
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// Builds the command line for a request.
+fn command_line(method: &str, params: &Value) -> Result<String, String> {
+    if method == "execute" {
+        return match params.get("command") {
+            Some(Value::String(command)) => Ok(command.clone()),
+            _ => Err("execute: Expected params {\"command\": \"...\"}".to_string()),
+        };
+    }
+    fn to_arg(value: &Value) -> String {
+        match value {
+            Value::String(s) => s.clone(),
+            value => value.to_string(),
+        }
+    }
+    let mut line = method.to_string();
+    let (args, kwargs) = match params {
+        Value::Null => (&[][..], None),
+        Value::Array(args) => (&args[..], None),
+        Value::Object(kwargs) => match kwargs.get("args") {
+            Some(Value::Array(args)) => (&args[..], Some(kwargs)),
+            Some(_) => return Err(format!("{}: `args` must be an array", method)),
+            None => (&[][..], Some(kwargs)),
+        },
+        _ => return Err(format!("{}: params must be an array or an object", method)),
+    };
+    for arg in args {
+        line.push(' ');
+        line.push_str(&quote_arg(&to_arg(arg)));
+    }
+    for (key, value) in kwargs.into_iter().flatten() {
+        if key != "args" {
+            line.push(' ');
+            line.push_str(&quote_arg(&format!("{}={}", key, to_arg(value))));
+        }
+    }
+    Ok(line)
+}
+
+/// Serves requests from one client until it disconnects or asks for shutdown.
+/// Returns true on shutdown.
+fn serve_client(state: &mut GlobalState, reader: impl BufRead, writer: Writer) -> bool {
+    let sink_writer = writer.clone();
+    let output = Arc::new(Mutex::new(serde_json::Map::new()));
+    let sink_output = output.clone();
+    set_event_sink(Some(Box::new(move |mut event| {
+        let kind = event["event"].as_str().unwrap_or_default();
+        if RESULT_EVENTS.contains(&kind) {
+            if let Value::Object(mut fields) = event.take() {
+                fields.remove("event");
+                sink_output.lock().unwrap().extend(fields);
+            }
+            return;
+        }
+        send(
+            &sink_writer,
+            json!({ "jsonrpc": "2.0", "method": "event", "params": event }),
+        );
+    })));
+    let mut shutdown = false;
+    for line in reader.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Ok(request) = serde_json::from_str::<Value>(&line) else {
+            send(
+                &writer,
+                error_response(Value::Null, PARSE_ERROR, "Parse error"),
+            );
+            continue;
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            send(
+                &writer,
+                error_response(id, INVALID_REQUEST, "Invalid request"),
+            );
+            continue;
+        };
+        // notifications are run too, but get no response
+        let is_notification = request.get("id").is_none();
+        if method == "shutdown" {
+            if !is_notification {
+                send(
+                    &writer,
+                    json!({ "jsonrpc": "2.0", "id": id, "result": null }),
+                );
+            }
+            shutdown = true;
+            break;
+        }
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let response = match command_line(method, &params)
+            .and_then(|line| line.parse::<InputCommand>().map_err(|err| err.to_string()))
+        {
+            Err(message) => error_response(id, INVALID_PARAMS, &message),
+            Ok(cmd) => match cmd.server_unsupported() {
+                Some(message) => error_response(id, INVALID_PARAMS, message),
+                None => {
+                    let executed = state.execute(&cmd);
+                    let mut result = std::mem::take(&mut *output.lock().unwrap());
+                    match executed {
+                        Ok(()) => {
+                            result.insert("failed".to_string(), state.failed.into());
+                            json!({ "jsonrpc": "2.0", "id": id, "result": result })
+                        }
+                        Err(err) => error_response(id, EXECUTE_ERROR, &err.to_string()),
+                    }
+                }
+            },
+        };
+        if state.ctrlc_channel.try_recv().is_ok() {
+            state.ctrlc_channel.try_iter().count();
+        }
+        if !is_notification {
+            send(&writer, response);
+        }
+    }
+    set_event_sink(None);
+    shutdown
+}
+
+/// Runs the server. `args` is either empty for stdio, or `--socket <path>` for a Unix socket
+/// accepting one client at a time; the session is kept between clients.
+pub(crate) fn run(args: &[String]) -> anyhow::Result<()> {
+    let socket = match args {
+        [] => None,
+        [flag, path] if flag == "--socket" => Some(path.clone()),
+        _ => anyhow::bail!("Usage: gaboja serve [--socket <path>]"),
+    };
+    set_json_output(true);
+    let mut state = GlobalState::new(true)?;
+    // the start script in boj.toml may have run `set output text`
+    set_json_output(true);
+    let result = match socket {
+        None => protocol_stdout().map(|stdout| {
+            let writer: Writer = Arc::new(Mutex::new(stdout));
+            serve_client(&mut state, std::io::stdin().lock(), writer);
+        }),
+        Some(path) => serve_socket(&mut state, &path),
+    };
+    state.quit()?;
+    result
+}
+
+/// Keeps the real stdout for the protocol and points stdout at stderr,
+/// so that other output, including that of child processes, cannot break the messages.
+#[cfg(unix)]
+fn protocol_stdout() -> anyhow::Result<Box<dyn Write + Send>> {
+    use std::os::fd::AsFd;
+    std::io::stdout().flush()?;
+    let protocol = std::io::stdout().as_fd().try_clone_to_owned()?;
+    // SAFETY: both are standard file descriptors that stay open
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } == -1 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(Box::new(std::fs::File::from(protocol)))
+}
+
+#[cfg(not(unix))]
+fn protocol_stdout() -> anyhow::Result<Box<dyn Write + Send>> {
+    Ok(Box::new(std::io::stdout()))
+}
+
+#[cfg(unix)]
+fn serve_socket(state: &mut GlobalState, path: &str) -> anyhow::Result<()> {
+    use std::io::BufReader;
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!("{} exists and is not a socket", path);
+        }
+        if UnixStream::connect(path).is_ok() {
+            anyhow::bail!("A server is already listening on {}", path);
+        }
+        // left by a server that did not exit cleanly
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    println!("Listening on {}", path);
+    let mut result = Ok(());
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                result = Err(err.into());
+                break;
+            }
+        };
+        let writer: Writer = match stream.try_clone() {
+            Ok(writer) => Arc::new(Mutex::new(Box::new(writer))),
+            Err(_) => continue,
+        };
+        if serve_client(state, BufReader::new(stream), writer) {
+            break;
+        }
+    }
+    std::fs::remove_file(path).ok();
+    result
+}
+
+#[cfg(not(unix))]
+fn serve_socket(_state: &mut GlobalState, _path: &str) -> anyhow::Result<()> {
+    anyhow::bail!("Unix sockets are not supported on this platform; use stdio instead")
+}